@@ -37,14 +37,23 @@
 (import "env" "sndGes" (func $sndGes (param i32) (result f32)))
 (import "env" "blitSprite" (func $blitSprite (param i32) (param i32) (param i32) (param i32) (param i32)))
 (import "env" "grabSprite" (func $grabSprite (param i32) (param i32) (param i32) (param i32) (param i32)))
+(import "env" "isPlayerButtonPressed" (func $isPlayerButtonPressed (param i32) (param i32) (result i32)))
+(import "env" "isPlayerButtonTriggered" (func $isPlayerButtonTriggered (param i32) (param i32) (result i32)))
+(import "env" "mouseX" (func $mouseX (result i32)))
+(import "env" "mouseY" (func $mouseY (result i32)))
+(import "env" "isMouseButtonPressed" (func $isMouseButtonPressed (param i32) (result i32)))
+(import "env" "isMouseButtonTriggered" (func $isMouseButtonTriggered (param i32) (result i32)))
+(import "env" "readChar" (func $readChar (result i32)))
 
 ;; to use defines, include this file with a preprocessor
 ;; like gpp (https://logological.org/gpp).
 #define TIME_MS 0x40;
 #define GAMEPAD 0x44;
+#define MOUSE 0x70;
 #define FRAMEBUFFER 0x78;
 #define PALETTE 0x13000;
 #define FONT 0x13400;
+#define KEYBOARD 0x13c00;
 #define USER_MEM 0x14000;
 #define BUTTON_UP 0x0;
 #define BUTTON_DOWN 0x1;
@@ -54,3 +63,6 @@
 #define BUTTON_B 0x5;
 #define BUTTON_X 0x6;
 #define BUTTON_Y 0x7;
+#define MOUSE_BUTTON_LEFT 0x0;
+#define MOUSE_BUTTON_RIGHT 0x1;
+#define MOUSE_BUTTON_MIDDLE 0x2;
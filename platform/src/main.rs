@@ -30,24 +30,7 @@ fn main() -> Result<()> {
 }
 
 fn convert_font() -> Result<()> {
-    let image = lodepng::decode32_file("src/font.png")?;
-
-    assert!(image.width == 128 && image.height == 128);
-
-    let mut font = vec![];
-    for char in 0..256 {
-        for y in 0..8 {
-            let mut byte = 0u8;
-            let base = (char % 16 * 8) + (char / 16 * 8 + y) * 128;
-            for x in 0..8 {
-                byte += byte;
-                if image.buffer[base + x].r > 128 {
-                    byte |= 1;
-                }
-            }
-            font.push(byte);
-        }
-    }
+    let font = uw8_tool::convert_font(&std::fs::read("src/font.png")?)?;
 
     File::create("target/font.bin")?.write_all(&font)?;
 
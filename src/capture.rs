@@ -0,0 +1,139 @@
+use anyhow::Result;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// Frame delays in 1/100s for recording at 30fps, which GIF can't represent exactly.
+const GIF_DELAYS: [u16; 3] = [3, 3, 4];
+
+/// Saves screenshots and animated GIFs of the running cart next to the cart file,
+/// using the palette of the cart.
+pub(crate) struct Capture {
+    base: PathBuf,
+    gif: Option<GifRecording>,
+    start_gif: bool,
+}
+
+struct GifRecording {
+    path: PathBuf,
+    encoder: gif::Encoder<BufWriter<File>>,
+    palette: Vec<u8>,
+    frame: u32,
+}
+
+impl Capture {
+    pub fn new(cart_path: &Path) -> Capture {
+        Capture {
+            base: cart_path.with_extension(""),
+            gif: None,
+            start_gif: false,
+        }
+    }
+
+    /// Starts recording a GIF with the next frame or stops the current recording.
+    pub fn toggle_gif(&mut self) {
+        if let Some(gif) = self.gif.take() {
+            // the encoder writes the trailer when dropped
+            drop(gif.encoder);
+            println!("Saved {}", gif.path.display());
+        } else {
+            self.start_gif = !self.start_gif;
+        }
+    }
+
+    /// Saves a screenshot if requested and adds newly run frames to the GIF recording.
+    pub fn frame(&mut self, framebuffer: &[u8], palette: &[u8], screenshot: bool, new_frame: bool) {
+        if screenshot {
+            match self.screenshot(framebuffer, palette) {
+                Ok(path) => println!("Saved {}", path.display()),
+                Err(err) => eprintln!("Failed to save screenshot: {}", err),
+            }
+        }
+        if new_frame {
+            if let Err(err) = self.add_gif_frame(framebuffer, palette) {
+                eprintln!("Failed to record GIF: {}", err);
+                self.gif = None;
+            }
+        }
+    }
+
+    fn screenshot(&self, framebuffer: &[u8], palette: &[u8]) -> Result<PathBuf> {
+        let mut encoder = lodepng::Encoder::new();
+        encoder.set_auto_convert(false);
+        set_palette(encoder.info_raw_mut(), palette)?;
+        set_palette(&mut encoder.info_png_mut().color, palette)?;
+        let path = self.next_free_path("png");
+        encoder.encode_file(&path, framebuffer, 320, 240)?;
+        Ok(path)
+    }
+
+    fn add_gif_frame(&mut self, framebuffer: &[u8], palette: &[u8]) -> Result<()> {
+        let palette = rgb_palette(palette);
+        if self.start_gif {
+            self.start_gif = false;
+            let path = self.next_free_path("gif");
+            let mut encoder =
+                gif::Encoder::new(BufWriter::new(File::create(&path)?), 320, 240, &palette)?;
+            encoder.set_repeat(gif::Repeat::Infinite)?;
+            println!("Recording {}", path.display());
+            self.gif = Some(GifRecording {
+                path,
+                encoder,
+                palette: palette.clone(),
+                frame: 0,
+            });
+        }
+
+        let Some(ref mut gif) = self.gif else {
+            return Ok(());
+        };
+        gif.frame += 1;
+        if gif.frame % 2 == 0 {
+            return Ok(());
+        }
+        let mut frame = gif::Frame {
+            width: 320,
+            height: 240,
+            delay: GIF_DELAYS[(gif.frame / 2 % 3) as usize],
+            buffer: Cow::Borrowed(framebuffer),
+            ..Default::default()
+        };
+        if palette != gif.palette {
+            frame.palette = Some(palette);
+        }
+        gif.encoder.write_frame(&frame)?;
+        Ok(())
+    }
+
+    fn next_free_path(&self, extension: &str) -> PathBuf {
+        (1..)
+            .map(|index| {
+                PathBuf::from(format!(
+                    "{}-{:03}.{}",
+                    self.base.display(),
+                    index,
+                    extension
+                ))
+            })
+            .find(|path| !path.exists())
+            .unwrap()
+    }
+}
+
+fn set_palette(color: &mut lodepng::ColorMode, palette: &[u8]) -> Result<()> {
+    color.colortype = lodepng::ColorType::PALETTE;
+    color.set_bitdepth(8);
+    for rgba in palette.chunks_exact(4) {
+        color.palette_add(lodepng::RGBA::new(rgba[0], rgba[1], rgba[2], 255))?;
+    }
+    Ok(())
+}
+
+fn rgb_palette(palette: &[u8]) -> Vec<u8> {
+    palette
+        .chunks_exact(4)
+        .flat_map(|rgba| &rgba[..3])
+        .copied()
+        .collect()
+}
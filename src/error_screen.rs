@@ -0,0 +1,130 @@
+use std::sync::OnceLock;
+
+use crate::run_native::to_uw8_char;
+
+const WIDTH: usize = 320;
+const HEIGHT: usize = 240;
+const COLUMNS: usize = WIDTH / 8;
+const ROWS: usize = HEIGHT / 8;
+
+/// The 8x8 MicroW8 font, one byte per row with the leftmost pixel in the msb.
+fn font() -> &'static [u8] {
+    static FONT: OnceLock<Vec<u8>> = OnceLock::new();
+    FONT.get_or_init(|| {
+        uw8_tool::convert_font(include_bytes!("../platform/src/font.png"))
+            .expect("Failed to convert font")
+    })
+}
+
+/// Removes the ANSI escape sequences used to color terminal output.
+fn strip_ansi_escapes(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(char) = chars.next() {
+        if char != '\u{1b}' {
+            result.push(char);
+        } else if chars.next() == Some('[') {
+            // skip the parameters up to the final byte of the sequence
+            for char in chars.by_ref() {
+                if ('@'..='~').contains(&char) {
+                    break;
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Draws a single line of text into a 320x240 framebuffer at the given character
+/// cell, clipping at the right edge. Characters missing from the font are drawn as `?`.
+pub(crate) fn draw_text(framebuffer: &mut [u8], column: usize, row: usize, text: &str, color: u8) {
+    let font = font();
+    for (index, char) in text.chars().enumerate() {
+        let char = match to_uw8_char(char) {
+            Some(char) if char >= 32 => char,
+            _ => b'?',
+        };
+        let x = (column + index) * 8;
+        if x >= WIDTH || row >= ROWS {
+            break;
+        }
+        for y in 0..8 {
+            let bits = font[char as usize * 8 + y];
+            for bit in 0..8 {
+                if bits & (0x80 >> bit) != 0 {
+                    framebuffer[(row * 8 + y) * WIDTH + x + bit] = color;
+                }
+            }
+        }
+    }
+}
+
+/// A screen showing an error message in place of the cart, eg. after a trap.
+pub(crate) struct ErrorScreen {
+    framebuffer: Vec<u8>,
+    palette: Vec<u8>,
+}
+
+impl ErrorScreen {
+    pub fn new(title: &str, message: &str) -> ErrorScreen {
+        let mut framebuffer = vec![0; WIDTH * HEIGHT];
+        let mut palette = vec![0; 256 * 4];
+        palette[0..4].copy_from_slice(&[0x30, 0x08, 0x08, 0xff]);
+        palette[4..8].copy_from_slice(&[0xff, 0x60, 0x60, 0xff]);
+        palette[8..12].copy_from_slice(&[0xe0, 0xe0, 0xe0, 0xff]);
+
+        draw_text(&mut framebuffer, 0, 0, title, 1);
+        let message = strip_ansi_escapes(message);
+        let lines = message.lines().flat_map(|line| {
+            let line = line.replace('\t', "  ");
+            let chars: Vec<char> = line.chars().collect();
+            if chars.is_empty() {
+                vec![String::new()]
+            } else {
+                chars
+                    .chunks(COLUMNS)
+                    .map(|chunk| chunk.iter().collect())
+                    .collect()
+            }
+        });
+        for (row, line) in lines.take(ROWS - 2).enumerate() {
+            draw_text(&mut framebuffer, 0, row + 2, &line, 2);
+        }
+
+        ErrorScreen {
+            framebuffer,
+            palette,
+        }
+    }
+
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.framebuffer
+    }
+
+    pub fn palette(&self) -> &[u8] {
+        &self.palette
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_ansi_escapes_removes_colors() {
+        assert_eq!(
+            strip_ansi_escapes("\u{1b}[1;31merror\u{1b}[0m: trap"),
+            "error: trap"
+        );
+        assert_eq!(strip_ansi_escapes("plain text"), "plain text");
+    }
+
+    #[test]
+    fn draw_text_replaces_unknown_characters() {
+        let mut expected = vec![0; WIDTH * HEIGHT];
+        draw_text(&mut expected, 0, 0, "a?b", 1);
+        let mut framebuffer = vec![0; WIDTH * HEIGHT];
+        draw_text(&mut framebuffer, 0, 0, "a→b", 1);
+        assert!(framebuffer == expected);
+    }
+}
@@ -0,0 +1,33 @@
+/// 64bit FNV-1a hash, used to identify carts and to compare audio output.
+#[derive(Debug, Clone, Copy)]
+pub struct Fnv1a(u64);
+
+impl Fnv1a {
+    pub fn new() -> Fnv1a {
+        Fnv1a(0xcbf29ce484222325)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for Fnv1a {
+    fn default() -> Fnv1a {
+        Fnv1a::new()
+    }
+}
+
+/// The FNV-1a hash of `data`.
+pub fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash = Fnv1a::new();
+    hash.write(data);
+    hash.finish()
+}
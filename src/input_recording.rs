@@ -0,0 +1,358 @@
+use anyhow::{anyhow, bail, Result};
+use std::fs::File;
+use std::io::{prelude::*, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::fnv::fnv1a;
+
+const HEADER: &str = "uw8-input 1";
+
+/// The mouse state written to 0x70: the position in screen pixels, which can be
+/// outside of the screen, and the pressed buttons (left, right, middle).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MouseState {
+    pub x: i16,
+    pub y: i16,
+    pub buttons: u8,
+}
+
+impl MouseState {
+    pub fn to_bytes(&self) -> [u8; 5] {
+        let [x0, x1] = self.x.to_le_bytes();
+        let [y0, y1] = self.y.to_le_bytes();
+        [x0, x1, y0, y1, self.buttons]
+    }
+}
+
+/// The input of a single frame: the four gamepad bytes written to 0x44, the mouse
+/// state, the characters typed since the last frame and whether the cart was reset
+/// before the frame.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputFrame {
+    pub gamepads: [u8; 4],
+    pub mouse: MouseState,
+    /// Typed characters in the MicroW8 character set.
+    pub text: Vec<u8>,
+    pub reset: bool,
+}
+
+/// Records the per-frame input to a file, flushing every frame so that the recording
+/// survives a crashing cart.
+///
+/// The file is a text file with a header, the hash of the recorded cart and then one
+/// line per frame containing the gamepad bytes as hex, followed by ` r` for frames
+/// with a reset, ` mX,Y,BUTTONS` when the mouse state is not all zero and ` t` followed
+/// by the typed characters as hex.
+///
+/// When a different cart is loaded (eg. in watch mode), the recording continues in a new
+/// file with an increasing number appended to the name, eg. `input-2.txt`, so the
+/// previous recordings stay intact.
+pub struct InputRecorder {
+    path: PathBuf,
+    out: Option<BufWriter<File>>,
+    cart_hash: u64,
+    num_recordings: u32,
+}
+
+impl InputRecorder {
+    pub fn new<P: Into<PathBuf>>(path: P) -> InputRecorder {
+        InputRecorder {
+            path: path.into(),
+            out: None,
+            cart_hash: 0,
+            num_recordings: 0,
+        }
+    }
+
+    /// Starts a new recording unless we are already recording this cart.
+    pub fn start(&mut self, cart: &[u8]) -> Result<()> {
+        let cart_hash = fnv1a(cart);
+        if self.out.is_some() && self.cart_hash == cart_hash {
+            return Ok(());
+        }
+
+        self.num_recordings += 1;
+        let path = if self.num_recordings == 1 {
+            self.path.clone()
+        } else {
+            let mut name = self.path.file_stem().unwrap_or_default().to_owned();
+            name.push(format!("-{}", self.num_recordings));
+            if let Some(extension) = self.path.extension() {
+                name.push(".");
+                name.push(extension);
+            }
+            let path = self.path.with_file_name(name);
+            println!("Cart changed, recording input to {}", path.display());
+            path
+        };
+
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", HEADER)?;
+        writeln!(out, "cart {:016x}", cart_hash)?;
+        out.flush()?;
+
+        self.out = Some(out);
+        self.cart_hash = cart_hash;
+        Ok(())
+    }
+
+    pub fn record(&mut self, frame: &InputFrame) -> Result<()> {
+        if let Some(ref mut out) = self.out {
+            for byte in frame.gamepads {
+                write!(out, "{:02x}", byte)?;
+            }
+            if frame.reset {
+                write!(out, " r")?;
+            }
+            let mouse = frame.mouse;
+            if mouse != MouseState::default() {
+                write!(out, " m{},{},{}", mouse.x, mouse.y, mouse.buttons)?;
+            }
+            if !frame.text.is_empty() {
+                write!(out, " t")?;
+                for byte in &frame.text {
+                    write!(out, "{:02x}", byte)?;
+                }
+            }
+            writeln!(out)?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// A previously recorded input file, played back frame by frame.
+pub struct InputReplay {
+    cart_hash: u64,
+    frames: Vec<InputFrame>,
+    position: usize,
+}
+
+impl InputReplay {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<InputReplay> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            bail!("Not a MicroW8 input recording");
+        }
+
+        let cart_hash = lines
+            .next()
+            .transpose()?
+            .and_then(|line| {
+                line.strip_prefix("cart ")
+                    .filter(|hash| hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .and_then(|hash| u64::from_str_radix(hash, 16).ok())
+            })
+            .ok_or_else(|| anyhow!("Input recording is missing the cart hash"))?;
+
+        let mut frames = vec![];
+        for (index, line) in lines.enumerate() {
+            let line = line?;
+            let mut parts = line.split_whitespace();
+            let gamepads = parts
+                .next()
+                .filter(|hex| hex.len() == 8 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Invalid gamepad state in line {}, expected 8 hex digits",
+                        index + 3
+                    )
+                })?;
+            let mut reset = false;
+            let mut mouse = MouseState::default();
+            let mut text = vec![];
+            for part in parts {
+                if part == "r" {
+                    reset = true;
+                } else if let Some(state) = part.strip_prefix('m') {
+                    mouse = parse_mouse(state)
+                        .ok_or_else(|| anyhow!("Invalid mouse state in line {}", index + 3))?;
+                } else if let Some(hex) = part.strip_prefix('t') {
+                    text = parse_hex_bytes(hex)
+                        .ok_or_else(|| anyhow!("Invalid text in line {}", index + 3))?;
+                }
+            }
+            frames.push(InputFrame {
+                gamepads: gamepads.to_be_bytes(),
+                mouse,
+                text,
+                reset,
+            });
+        }
+
+        Ok(InputReplay {
+            cart_hash,
+            frames,
+            position: 0,
+        })
+    }
+
+    /// Fails if the recording was not made with the given cart.
+    pub fn check_cart(&self, cart: &[u8]) -> Result<()> {
+        let cart_hash = fnv1a(cart);
+        if cart_hash != self.cart_hash {
+            bail!(
+                "Input recording was made with a different cart (hash {:016x}, expected {:016x})",
+                cart_hash,
+                self.cart_hash
+            );
+        }
+        Ok(())
+    }
+
+    /// The number of recorded frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Whether all recorded frames have been played back.
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.frames.len()
+    }
+
+    /// Returns the input for the next frame or `None` once the recording is exhausted.
+    pub fn next_frame(&mut self) -> Option<InputFrame> {
+        let frame = self.frames.get(self.position).cloned();
+        if frame.is_some() {
+            self.position += 1;
+        }
+        frame
+    }
+}
+
+fn parse_mouse(state: &str) -> Option<MouseState> {
+    let mut values = state.split(',');
+    let mouse = MouseState {
+        x: values.next()?.parse().ok()?,
+        y: values.next()?.parse().ok()?,
+        buttons: values.next()?.parse().ok()?,
+    };
+    values.next().is_none().then_some(mouse)
+}
+
+fn parse_hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, contents: &str) -> Result<InputReplay> {
+        let path = std::env::temp_dir().join(format!(
+            "uw8-input-test-{}-{}.txt",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, contents)?;
+        let replay = InputReplay::load(&path);
+        let _ = std::fs::remove_file(&path);
+        replay
+    }
+
+    #[test]
+    fn load_parses_frames() {
+        let mut replay = load(
+            "frames",
+            "uw8-input 1\ncart 0123456789abcdef\n01000000\n00000080 r m-3,200,1 t410a\n",
+        )
+        .unwrap();
+        assert_eq!(replay.cart_hash, 0x0123456789abcdef);
+        assert_eq!(replay.len(), 2);
+        assert_eq!(
+            replay.next_frame(),
+            Some(InputFrame {
+                gamepads: [1, 0, 0, 0],
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            replay.next_frame(),
+            Some(InputFrame {
+                gamepads: [0, 0, 0, 0x80],
+                mouse: MouseState {
+                    x: -3,
+                    y: 200,
+                    buttons: 1
+                },
+                text: vec![b'A', 10],
+                reset: true,
+            })
+        );
+        assert!(replay.is_finished());
+        assert_eq!(replay.next_frame(), None);
+    }
+
+    #[test]
+    fn load_checks_header_and_cart_hash() {
+        assert!(load("header", "uw8-input 2\ncart 0123456789abcdef\n").is_err());
+        assert!(load("missing-hash", "uw8-input 1\n").is_err());
+        assert!(load("short-hash", "uw8-input 1\ncart 123\n").is_err());
+        assert!(load("long-hash", "uw8-input 1\ncart 0123456789abcdef0\n").is_err());
+        assert!(load("signed-hash", "uw8-input 1\ncart +123456789abcdef\n").is_err());
+    }
+
+    #[test]
+    fn load_rejects_invalid_frames() {
+        let header = "uw8-input 1\ncart 0123456789abcdef\n";
+        assert!(load("gamepads", &format!("{}xyz\n", header)).is_err());
+        assert!(load("short-gamepads", &format!("{}1\n", header)).is_err());
+        assert!(load("long-gamepads", &format!("{}000000001\n", header)).is_err());
+        assert!(load("signed-gamepads", &format!("{}+0000001\n", header)).is_err());
+        let Err(err) = load("gamepads-line", &format!("{}00000000\n0000000g\n", header)) else {
+            panic!("invalid gamepad state accepted");
+        };
+        assert!(err.to_string().contains("line 4"), "{}", err);
+        assert!(load("mouse", &format!("{}00000000 m1,2\n", header)).is_err());
+        assert!(load("text", &format!("{}00000000 t4\n", header)).is_err());
+    }
+
+    #[test]
+    fn recorder_keeps_recording_of_previous_cart() {
+        let dir = std::env::temp_dir().join(format!("uw8-recorder-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut recorder = InputRecorder::new(dir.join("input.txt"));
+        let frame = InputFrame {
+            gamepads: [1, 2, 3, 4],
+            ..Default::default()
+        };
+        recorder.start(b"cart").unwrap();
+        recorder.record(&frame).unwrap();
+        recorder.start(b"cart").unwrap();
+        recorder.record(&frame).unwrap();
+        recorder.start(b"changed cart").unwrap();
+        recorder.record(&frame).unwrap();
+
+        let first = InputReplay::load(dir.join("input.txt")).unwrap();
+        let second = InputReplay::load(dir.join("input-2.txt")).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(first.len(), 2);
+        assert!(first.check_cart(b"cart").is_ok());
+        assert_eq!(second.len(), 1);
+        assert!(second.check_cart(b"changed cart").is_ok());
+    }
+
+    #[test]
+    fn check_cart_compares_hash() {
+        let cart = b"cart";
+        let replay = load(
+            "check",
+            &format!("uw8-input 1\ncart {:016x}\n", fnv1a(cart)),
+        )
+        .unwrap();
+        assert!(replay.check_cart(cart).is_ok());
+        assert!(replay.check_cart(b"other cart").is_err());
+    }
+}
@@ -1,14 +1,38 @@
+#[cfg(feature = "native")]
+mod capture;
+#[cfg(feature = "native")]
+mod error_screen;
 mod filewatcher;
+mod fnv;
+mod input_recording;
+mod memory_inspector;
+#[cfg(feature = "native")]
+mod profiler;
+mod record;
+#[cfg(feature = "native")]
+mod run_headless;
 #[cfg(feature = "native")]
 mod run_native;
 #[cfg(feature = "browser")]
 mod run_web;
+#[cfg(feature = "native")]
+mod sampling_profiler;
+#[cfg(feature = "native")]
+mod trap_report;
 
 pub use filewatcher::FileWatcher;
+pub use fnv::Fnv1a;
+pub use input_recording::{InputFrame, InputRecorder, InputReplay, MouseState};
+pub use memory_inspector::{MemoryInspector, MemoryRange};
+pub use record::{write_wav, VideoWriter};
 #[cfg(feature = "native")]
-pub use run_native::MicroW8;
+pub use run_headless::Headless;
+#[cfg(feature = "native")]
+pub use run_native::{MicroW8, SaveState, Timeouts};
 #[cfg(feature = "browser")]
 pub use run_web::RunWebServer;
+#[cfg(feature = "native")]
+pub use trap_report::TrapReport;
 
 use anyhow::Result;
 
@@ -16,4 +40,6 @@ pub trait Runtime {
     fn is_open(&self) -> bool;
     fn load(&mut self, module_data: &[u8]) -> Result<()>;
     fn run_frame(&mut self) -> Result<()>;
+    /// Shows an error in place of the cart until the next successful load.
+    fn show_error(&mut self, _message: &str) {}
 }
@@ -27,6 +27,14 @@ fn main() -> Result<()> {
         }
         #[cfg(any(feature = "native", feature = "browser"))]
         Some("run") => run(args),
+        #[cfg(feature = "native")]
+        Some("render") => render(args),
+        #[cfg(feature = "native")]
+        Some("record") => record(args),
+        #[cfg(feature = "native")]
+        Some("test") => test(args),
+        #[cfg(feature = "native")]
+        Some("profile") => profile(args),
         Some("pack") => pack(args),
         Some("unpack") => unpack(args),
         Some("compile") => compile(args),
@@ -36,7 +44,15 @@ fn main() -> Result<()> {
             println!();
             println!("Usage:");
             #[cfg(any(feature = "native", feature = "browser"))]
-            println!("  uw8 run [-t/--timeout <frames>] [--b/--browser] [-w/--watch] [-p/--pack] [-u/--uncompressed] [-l/--level] [-o/--output <out-file>] <file>");
+            println!("  uw8 run [-t/--timeout <frames|Nms>] [--start-timeout <frames|Nms>] [--snd-timeout <frames|Nms>] [--b/--browser] [-w/--watch] [-p/--pack] [-u/--uncompressed] [-l/--level] [-o/--output <out-file>] [-d/--debug] [--record-input/--replay-input <file>] [--hot-reload [--keep-screen]] [--inspect <range>] [--timings] <file>");
+            #[cfg(feature = "native")]
+            println!("  uw8 render [-t/--timeout <frames|Nms>] [-n/--num-frames <n>] [-f/--frames <f1,f2,..>] [-e/--every <n>] [-o/--output <prefix>] [--replay-input <file>] <file>");
+            #[cfg(feature = "native")]
+            println!("  uw8 record [-t/--timeout <frames|Nms>] [-s/--seconds <n>] [-m/--no-audio] [-o/--output <base>] [--replay-input <file>] <file>");
+            #[cfg(feature = "native")]
+            println!("  uw8 test [-t/--timeout <frames|Nms>] [--no-timeout] [-n/--num-frames <n>] [-a/--audio] [--bless] [<file or dir>..]");
+            #[cfg(feature = "native")]
+            println!("  uw8 profile [-t/--timeout <frames|Nms>] [-n/--num-frames <n>] [-i/--interval <us>] [-o/--output <file>] <file>");
             println!("  uw8 pack [-u/--uncompressed] [-l/--level] <in-file> <out-file>");
             println!("  uw8 unpack <in-file> <out-file>");
             println!("  uw8 compile [-d/--debug] <in-file> <out-file>");
@@ -53,8 +69,8 @@ fn main() -> Result<()> {
 #[cfg(any(feature = "native", feature = "browser"))]
 fn run(mut args: Arguments) -> Result<()> {
     let watch_mode = args.contains(["-w", "--watch"]);
-    #[allow(unused)]
-    let timeout: Option<u32> = args.opt_value_from_str(["-t", "--timeout"])?;
+    #[cfg(feature = "native")]
+    let timeouts = parse_timeouts(&mut args)?;
 
     let mut config = Config::default();
     if args.contains(["-p", "--pack"]) {
@@ -76,6 +92,9 @@ fn run(mut args: Arguments) -> Result<()> {
         config.output_path = Some(path);
     }
 
+    // include function names for trap reports
+    config.debug = args.contains(["-d", "--debug"]);
+
     #[cfg(feature = "native")]
     let run_browser = args.contains(["-b", "--browser"]);
     #[cfg(not(feature = "native"))]
@@ -84,6 +103,24 @@ fn run(mut args: Arguments) -> Result<()> {
     #[allow(unused)]
     let disable_audio = args.contains(["-m", "--no-audio"]);
 
+    #[allow(unused)]
+    let record_input: Option<PathBuf> =
+        args.opt_value_from_os_str::<_, _, bool>("--record-input", |s| Ok(s.into()))?;
+    #[allow(unused)]
+    let replay_input: Option<PathBuf> =
+        args.opt_value_from_os_str::<_, _, bool>("--replay-input", |s| Ok(s.into()))?;
+
+    #[allow(unused)]
+    let save_states = args.contains("--save-states");
+    #[allow(unused)]
+    let hot_reload = args.contains("--hot-reload");
+    #[allow(unused)]
+    let keep_screen = args.contains("--keep-screen");
+    #[allow(unused)]
+    let inspect_ranges: Vec<uw8::MemoryRange> = args.values_from_str("--inspect")?;
+    #[allow(unused)]
+    let timings = args.contains("--timings");
+
     #[cfg(feature = "native")]
     let window_config = {
         let mut config = uw8_window::WindowConfig::default();
@@ -104,10 +141,32 @@ fn run(mut args: Arguments) -> Result<()> {
         unimplemented!();
         #[cfg(feature = "native")]
         {
-            let mut microw8 = MicroW8::new(timeout, window_config)?;
+            let mut microw8 = MicroW8::new(timeouts, window_config)?;
+            microw8.enable_capture(&filename);
+            if let Some(name) = filename.file_name() {
+                microw8.set_cart_name(&name.to_string_lossy());
+            }
             if disable_audio {
                 microw8.disable_audio();
             }
+            if let Some(path) = record_input {
+                microw8.record_input(uw8::InputRecorder::new(path));
+            }
+            if let Some(path) = replay_input {
+                microw8.replay_input(uw8::InputReplay::load(path)?);
+            }
+            if save_states {
+                microw8.enable_save_states();
+            }
+            if hot_reload {
+                microw8.enable_hot_reload(keep_screen);
+            }
+            if timings {
+                microw8.enable_profiler();
+            }
+            if !inspect_ranges.is_empty() {
+                microw8.set_inspector_ranges(inspect_ranges);
+            }
             Box::new(microw8)
         }
     } else {
@@ -132,6 +191,7 @@ fn run(mut args: Arguments) -> Result<()> {
                 if !watch_mode {
                     exit(1);
                 }
+                runtime.show_error(&format!("{:#}", err));
             }
             first_run = false;
         }
@@ -147,10 +207,412 @@ fn run(mut args: Arguments) -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "native")]
+fn render(mut args: Arguments) -> Result<()> {
+    let timeouts = parse_timeouts(&mut args)?;
+    let num_frames: Option<u32> = args.opt_value_from_str(["-n", "--num-frames"])?;
+    let frames: Option<Vec<u32>> = args.opt_value_from_fn(["-f", "--frames"], |s| {
+        s.split(',')
+            .map(|f| f.trim().parse::<u32>())
+            .collect::<Result<Vec<u32>, _>>()
+    })?;
+    let every: Option<u32> = args.opt_value_from_str(["-e", "--every"])?;
+    let output: Option<PathBuf> =
+        args.opt_value_from_os_str::<_, _, bool>(["-o", "--output"], |s| Ok(s.into()))?;
+    let replay_input: Option<PathBuf> =
+        args.opt_value_from_os_str::<_, _, bool>("--replay-input", |s| Ok(s.into()))?;
+
+    let filename = args.free_from_os_str::<PathBuf, bool>(|s| Ok(s.into()))?;
+
+    if every == Some(0) {
+        anyhow::bail!("--every needs to be at least 1");
+    }
+
+    let replay = replay_input.map(uw8::InputReplay::load).transpose()?;
+
+    let num_frames = num_frames.unwrap_or_else(|| {
+        frames
+            .as_ref()
+            .and_then(|frames| frames.iter().max())
+            .map(|&max| max + 1)
+            .or_else(|| replay.as_ref().map(|replay| replay.len() as u32))
+            .unwrap_or(1)
+    });
+    let output = output.unwrap_or_else(|| filename.with_extension(""));
+
+    let cart = load_cart(&filename, &Config::default()).0?;
+
+    let mut headless = uw8::Headless::new(timeouts)?;
+    if let Some(replay) = replay {
+        headless.replay_input(replay);
+    }
+    headless.load(&cart)?;
+
+    for frame in 0..num_frames {
+        headless.run_frame()?;
+
+        let selected = match (&frames, every) {
+            (None, None) => frame + 1 == num_frames,
+            (frames, every) => {
+                frames.as_ref().is_some_and(|f| f.contains(&frame))
+                    || every.is_some_and(|every| frame % every == 0)
+            }
+        };
+
+        if selected {
+            let mut path = output.clone().into_os_string();
+            path.push(format!("-{:05}.png", frame));
+            lodepng::encode24_file(&path, &headless.rgb_frame(), 320, 240)?;
+            println!("Wrote {}", Path::new(&path).display());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "native")]
+fn record(mut args: Arguments) -> Result<()> {
+    let timeouts = parse_timeouts(&mut args)?;
+    let seconds: Option<f32> = args.opt_value_from_str(["-s", "--seconds"])?;
+    let disable_audio = args.contains(["-m", "--no-audio"]);
+    let output: Option<PathBuf> =
+        args.opt_value_from_os_str::<_, _, bool>(["-o", "--output"], |s| Ok(s.into()))?;
+    let replay_input: Option<PathBuf> =
+        args.opt_value_from_os_str::<_, _, bool>("--replay-input", |s| Ok(s.into()))?;
+
+    let filename = args.free_from_os_str::<PathBuf, bool>(|s| Ok(s.into()))?;
+
+    let replay = replay_input.map(uw8::InputReplay::load).transpose()?;
+
+    let output = output.unwrap_or_else(|| filename.with_extension(""));
+    let num_frames = match (seconds, &replay) {
+        (Some(seconds), _) => (seconds * 60.0).round() as u32,
+        (None, Some(replay)) => replay.len() as u32,
+        (None, None) => 600,
+    };
+
+    let cart = load_cart(&filename, &Config::default()).0?;
+
+    let mut headless = uw8::Headless::new(timeouts)?;
+    if !disable_audio {
+        headless.enable_audio();
+    }
+    if let Some(replay) = replay {
+        headless.replay_input(replay);
+    }
+    headless.load(&cart)?;
+
+    let video_path = output.with_extension("y4m");
+    let mut video = uw8::VideoWriter::create(&video_path)?;
+    let mut audio = Vec::new();
+
+    for _ in 0..num_frames {
+        headless.run_frame()?;
+        video.write_frame(&headless.rgb_frame())?;
+        audio.extend_from_slice(headless.audio());
+    }
+
+    video.finish()?;
+    println!("Wrote {}", video_path.display());
+
+    if !disable_audio {
+        let audio_path = output.with_extension("wav");
+        uw8::write_wav(&audio_path, &audio)?;
+        println!("Wrote {}", audio_path.display());
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "native")]
+fn test(mut args: Arguments) -> Result<()> {
+    use ansi_term::Colour;
+
+    let timeouts = parse_timeouts(&mut args)?;
+    // a hanging cart fails its test instead of blocking the whole run
+    let timeouts = if args.contains("--no-timeout") {
+        if timeouts.is_some() {
+            anyhow::bail!("--no-timeout can't be combined with other timeout options");
+        }
+        None
+    } else {
+        Some(timeouts.unwrap_or_default())
+    };
+    let num_frames: Option<u32> = args.opt_value_from_str(["-n", "--num-frames"])?;
+    let check_audio = args.contains(["-a", "--audio"]);
+    let bless = args.contains("--bless");
+
+    let mut paths = vec![];
+    while let Some(path) = args.opt_free_from_os_str::<PathBuf, bool>(|s| Ok(s.into()))? {
+        paths.push(path);
+    }
+    if paths.is_empty() {
+        paths.push(PathBuf::from("test"));
+    }
+
+    let mut carts = vec![];
+    for path in paths {
+        if path.is_dir() {
+            let mut dir_carts = vec![];
+            for entry in std::fs::read_dir(&path)? {
+                let entry = entry?.path();
+                if matches!(
+                    entry.extension().and_then(|e| e.to_str()),
+                    Some("cwa" | "wat" | "wast" | "uw8" | "wasm")
+                ) {
+                    dir_carts.push(entry);
+                }
+            }
+            dir_carts.sort();
+            carts.extend(dir_carts);
+        } else {
+            carts.push(path);
+        }
+    }
+
+    let mut num_failed = 0;
+    for cart in &carts {
+        match golden_test(cart, timeouts, num_frames, check_audio, bless) {
+            Ok(GoldenResult::Passed) => {
+                println!("{}: {}", cart.display(), Colour::Green.paint("ok"))
+            }
+            Ok(GoldenResult::Written) if bless => {
+                println!("{}: {}", cart.display(), Colour::Yellow.paint("blessed"))
+            }
+            Ok(GoldenResult::Written) => {
+                println!("{}: {}", cart.display(), Colour::Yellow.paint("created"))
+            }
+            Ok(GoldenResult::Failed(failure)) => {
+                println!(
+                    "{}: {} - {}",
+                    cart.display(),
+                    Colour::Red.paint("FAILED"),
+                    failure
+                );
+                num_failed += 1;
+            }
+            Err(err) => {
+                println!(
+                    "{}: {} - {}",
+                    cart.display(),
+                    Colour::Red.paint("ERROR"),
+                    err
+                );
+                num_failed += 1;
+            }
+        }
+    }
+
+    if num_failed > 0 {
+        anyhow::bail!("{} of {} tests failed", num_failed, carts.len());
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "native")]
+fn profile(mut args: Arguments) -> Result<()> {
+    let timeouts = parse_timeouts(&mut args)?;
+    let num_frames: u32 = args
+        .opt_value_from_str(["-n", "--num-frames"])?
+        .unwrap_or(600);
+    let interval: u64 = args
+        .opt_value_from_str(["-i", "--interval"])?
+        .unwrap_or(1000);
+    let output: Option<PathBuf> =
+        args.opt_value_from_os_str::<_, _, bool>(["-o", "--output"], |s| Ok(s.into()))?;
+
+    let filename = args.free_from_os_str::<PathBuf, bool>(|s| Ok(s.into()))?;
+
+    let output = output.unwrap_or_else(|| filename.with_extension("profile.json"));
+
+    let config = Config {
+        debug: true,
+        ..Default::default()
+    };
+    let cart = load_cart(&filename, &config).0?;
+
+    let mut headless = uw8::Headless::new(Some(timeouts.unwrap_or_default()))?;
+    headless.enable_profiling(std::time::Duration::from_micros(interval.max(1)));
+    headless.load(&cart)?;
+
+    // still write the profile up to the point where the cart trapped
+    let result = (0..num_frames).try_for_each(|_| headless.run_frame());
+
+    headless.finish_profile(std::io::BufWriter::new(File::create(&output)?))?;
+    println!("Wrote {}", output.display());
+
+    result
+}
+
+#[cfg(feature = "native")]
+enum GoldenResult {
+    Passed,
+    /// The golden files were missing or `--bless` was given.
+    Written,
+    Failed(String),
+}
+
+/// Runs a cart for `num_frames` and compares the last frame (and optionally a hash of
+/// all generated audio) against the golden files in the `golden` directory next to it.
+///
+/// Without `num_frames`, the frame count stored in `golden/NAME.frames` is used,
+/// defaulting to 60. Missing golden files are created from the current output.
+#[cfg(feature = "native")]
+fn golden_test(
+    cart_path: &Path,
+    timeouts: Option<uw8::Timeouts>,
+    num_frames: Option<u32>,
+    check_audio: bool,
+    bless: bool,
+) -> Result<GoldenResult> {
+    let golden_dir = cart_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("golden");
+    let name = cart_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let image_path = golden_dir.join(format!("{}.png", name));
+    let actual_path = golden_dir.join(format!("{}.actual.png", name));
+    let diff_path = golden_dir.join(format!("{}.diff.png", name));
+    let audio_path = golden_dir.join(format!("{}.audio", name));
+    let frames_path = golden_dir.join(format!("{}.frames", name));
+
+    let stored_num_frames = match std::fs::read_to_string(&frames_path) {
+        Ok(frames) => Some(frames.trim().parse::<u32>().map_err(|err| {
+            anyhow::anyhow!("Invalid frame count in {}: {}", frames_path.display(), err)
+        })?),
+        Err(_) => None,
+    };
+    let frame_count = num_frames.or(stored_num_frames).unwrap_or(60);
+
+    let cart = load_cart(cart_path, &Config::default()).0?;
+
+    let mut headless = uw8::Headless::new(timeouts)?;
+    if check_audio {
+        headless.enable_audio();
+    }
+    headless.load(&cart)?;
+
+    // hash of the 16bit samples
+    let mut audio_hash = uw8::Fnv1a::new();
+    for _ in 0..frame_count {
+        headless.run_frame()?;
+        for &sample in headless.audio() {
+            audio_hash.write(&((sample * 32767.0) as i16).to_le_bytes());
+        }
+    }
+    let audio_hash = audio_hash.finish();
+
+    let frame = headless.rgb_frame();
+
+    if bless || !image_path.exists() {
+        std::fs::create_dir_all(&golden_dir)?;
+        lodepng::encode24_file(&image_path, &frame, 320, 240)?;
+        if check_audio {
+            std::fs::write(&audio_path, format!("{:016x}\n", audio_hash))?;
+        }
+        if let Some(num_frames) = num_frames {
+            std::fs::write(&frames_path, format!("{}\n", num_frames))?;
+        }
+        let _ = std::fs::remove_file(&actual_path);
+        let _ = std::fs::remove_file(&diff_path);
+        return Ok(GoldenResult::Written);
+    }
+
+    let golden = lodepng::decode24_file(&image_path)?;
+    if golden.width != 320 || golden.height != 240 {
+        return Ok(GoldenResult::Failed(format!(
+            "{} is not 320x240",
+            image_path.display()
+        )));
+    }
+
+    let mut diff = Vec::with_capacity(frame.len());
+    let mut num_different = 0;
+    for (expected, actual) in golden.buffer.iter().zip(frame.chunks_exact(3)) {
+        if [expected.r, expected.g, expected.b] == actual {
+            // dimmed grayscale of the matching pixels for orientation
+            let gray = ((expected.r as u32 * 77 + expected.g as u32 * 150 + expected.b as u32 * 29)
+                >> 10) as u8;
+            diff.extend_from_slice(&[gray, gray, gray]);
+        } else {
+            diff.extend_from_slice(&[255, 0, 255]);
+            num_different += 1;
+        }
+    }
+
+    if num_different > 0 {
+        lodepng::encode24_file(&actual_path, &frame, 320, 240)?;
+        lodepng::encode24_file(&diff_path, &diff, 320, 240)?;
+        return Ok(GoldenResult::Failed(format!(
+            "{} pixels differ, see {}",
+            num_different,
+            diff_path.display()
+        )));
+    }
+
+    let _ = std::fs::remove_file(&actual_path);
+    let _ = std::fs::remove_file(&diff_path);
+
+    if check_audio {
+        let expected = match std::fs::read_to_string(&audio_path) {
+            Ok(hash) => u64::from_str_radix(hash.trim(), 16)?,
+            Err(_) => {
+                std::fs::write(&audio_path, format!("{:016x}\n", audio_hash))?;
+                return Ok(GoldenResult::Written);
+            }
+        };
+        if expected != audio_hash {
+            return Ok(GoldenResult::Failed(format!(
+                "audio hash {:016x} differs from {:016x}",
+                audio_hash, expected
+            )));
+        }
+    }
+
+    Ok(GoldenResult::Passed)
+}
+
 #[derive(Default)]
 struct Config {
     pack: Option<uw8_tool::PackConfig>,
     output_path: Option<PathBuf>,
+    debug: bool,
+}
+
+/// Parses the `-t/--timeout`, `--start-timeout` and `--snd-timeout` options.
+///
+/// Returns `None` when none of them is given, which disables the watchdog.
+#[cfg(feature = "native")]
+fn parse_timeouts(args: &mut Arguments) -> Result<Option<uw8::Timeouts>> {
+    let update = args.opt_value_from_fn(["-t", "--timeout"], parse_timeout)?;
+    let start = args.opt_value_from_fn("--start-timeout", parse_timeout)?;
+    let sound = args.opt_value_from_fn("--snd-timeout", parse_timeout)?;
+    if update.is_none() && start.is_none() && sound.is_none() {
+        return Ok(None);
+    }
+    let defaults = uw8::Timeouts::default();
+    Ok(Some(uw8::Timeouts {
+        start: start.unwrap_or(defaults.start),
+        update: update.unwrap_or(defaults.update),
+        sound: sound.unwrap_or(defaults.sound),
+    }))
+}
+
+/// Parses a timeout either in milliseconds (`250ms`) or in frames of 1/60s (`30`).
+#[cfg(feature = "native")]
+fn parse_timeout(s: &str) -> Result<std::time::Duration> {
+    use std::time::Duration;
+    if let Some(ms) = s.strip_suffix("ms") {
+        Ok(Duration::from_millis(ms.trim().parse()?))
+    } else {
+        let frames: u64 = s.parse()?;
+        Ok(Duration::from_millis(frames * 1000 / 60))
+    }
 }
 
 fn load_cart(filename: &Path, config: &Config) -> (Result<Vec<u8>>, Vec<PathBuf>) {
@@ -167,7 +629,11 @@ fn load_cart(filename: &Path, config: &Config) -> (Result<Vec<u8>>, Vec<PathBuf>
                 cart
             }
             SourceType::CurlyWas => {
-                let (module, deps) = curlywas::compile_file(filename, curlywas::Options::default());
+                let mut options = curlywas::Options::default();
+                if config.debug {
+                    options = options.with_debug();
+                }
+                let (module, deps) = curlywas::compile_file(filename, options);
                 *dependencies = deps;
                 module?
             }
@@ -273,6 +739,7 @@ fn pack(mut args: Arguments) -> Result<()> {
         &Config {
             pack: Some(pack_config),
             output_path: None,
+            debug: false,
         },
     )
     .0?;
@@ -312,3 +779,26 @@ fn filter_exports(mut args: Arguments) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn parse_timeout_accepts_frames_and_ms() {
+        assert_eq!(parse_timeout("30").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_timeout("1").unwrap(), Duration::from_millis(16));
+        assert_eq!(parse_timeout("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_timeout("250 ms").unwrap(), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn parse_timeout_rejects_invalid_values() {
+        assert!(parse_timeout("").is_err());
+        assert!(parse_timeout("-1").is_err());
+        assert!(parse_timeout("1.5").is_err());
+        assert!(parse_timeout("ms").is_err());
+        assert!(parse_timeout("10s").is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use anyhow::{anyhow, bail, Result};
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::str::FromStr;
+
+const MEMORY_SIZE: usize = 0x40000;
+const BYTES_PER_ROW: usize = 16;
+const ROWS_PER_PAGE: usize = 16;
+
+/// A named range of memory to show in the inspector.
+///
+/// Parses from the name of a region of the memory map (eg. `USER_MEM`), a hex range
+/// `START-END` or a hex start address and length `START+LENGTH`.
+#[derive(Debug, Clone)]
+pub struct MemoryRange {
+    name: String,
+    start: usize,
+    end: usize,
+}
+
+impl MemoryRange {
+    /// The regions of the memory map, each reaching up to the start of the next one.
+    pub fn named_regions() -> Vec<MemoryRange> {
+        let mut starts: Vec<(&str, u32)> = uw8_tool::memory_map().collect();
+        starts.push(("SOUND_REGS", 0x50));
+        starts.sort_by_key(|&(_, address)| address);
+
+        let mut regions = vec![];
+        for (index, &(name, start)) in starts.iter().enumerate() {
+            let end = starts
+                .get(index + 1)
+                .map_or(MEMORY_SIZE, |&(_, next)| next as usize);
+            regions.push(MemoryRange {
+                name: name.to_string(),
+                start: start as usize,
+                end,
+            });
+        }
+        regions
+    }
+}
+
+impl FromStr for MemoryRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<MemoryRange> {
+        if let Some(region) = MemoryRange::named_regions()
+            .into_iter()
+            .find(|region| region.name.eq_ignore_ascii_case(s))
+        {
+            return Ok(region);
+        }
+
+        fn parse_hex(s: &str) -> Result<usize> {
+            let s = s.trim_start_matches("0x");
+            usize::from_str_radix(s, 16).map_err(|_| anyhow!("Invalid hex number '{}'", s))
+        }
+
+        let (start, end) = if let Some((start, end)) = s.split_once('-') {
+            (parse_hex(start)?, parse_hex(end)?)
+        } else if let Some((start, length)) = s.split_once('+') {
+            let start = parse_hex(start)?;
+            (start, start.saturating_add(parse_hex(length)?))
+        } else {
+            bail!(
+                "Invalid memory range '{}', expected a region name, START-END or START+LENGTH",
+                s
+            );
+        };
+
+        if start >= end || end > MEMORY_SIZE {
+            bail!("Memory range '{}' is empty or out of bounds", s);
+        }
+
+        Ok(MemoryRange {
+            name: format!("{:05x}-{:05x}", start, end),
+            start,
+            end,
+        })
+    }
+}
+
+/// A live hex dump of the memory of the running cart, drawn to the terminal.
+///
+/// Bytes that changed during the last frame are highlighted.
+pub struct MemoryInspector {
+    ranges: Vec<MemoryRange>,
+    current: usize,
+    offset: usize,
+    visible: bool,
+    previous: Vec<u8>,
+}
+
+impl Default for MemoryInspector {
+    fn default() -> MemoryInspector {
+        MemoryInspector::new(MemoryRange::named_regions())
+    }
+}
+
+impl MemoryInspector {
+    pub fn new(ranges: Vec<MemoryRange>) -> MemoryInspector {
+        MemoryInspector {
+            ranges,
+            current: 0,
+            offset: 0,
+            visible: false,
+            previous: vec![],
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible && !self.ranges.is_empty()
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        if !self.visible {
+            // clear the terminal again
+            print!("\x1b[H\x1b[J");
+            let _ = std::io::stdout().flush();
+        }
+    }
+
+    pub fn next_range(&mut self) {
+        if !self.ranges.is_empty() {
+            self.current = (self.current + 1) % self.ranges.len();
+            self.offset = 0;
+        }
+    }
+
+    /// Scrolls the current range by the given number of pages.
+    pub fn scroll(&mut self, pages: i32) {
+        if let Some(range) = self.ranges.get(self.current) {
+            let page_size = (BYTES_PER_ROW * ROWS_PER_PAGE) as i64;
+            let max_offset = (range.end - range.start - 1) as i64 / page_size * page_size;
+            self.offset =
+                (self.offset as i64 + pages as i64 * page_size).clamp(0, max_offset) as usize;
+        }
+    }
+
+    /// Remembers the memory contents before running a frame.
+    pub fn begin_frame(&mut self, memory: &[u8]) {
+        if self.is_visible() {
+            self.previous.clear();
+            self.previous.extend_from_slice(memory);
+        }
+    }
+
+    pub fn draw(&self, memory: &[u8]) {
+        if !self.is_visible() {
+            return;
+        }
+        let range = &self.ranges[self.current];
+        let regions = MemoryRange::named_regions();
+
+        let mut out = String::new();
+        out.push_str("\x1b[H");
+        let _ = writeln!(
+            out,
+            "{} {:05x}-{:05x}  (F9: close, F10: next range, PgUp/PgDn: scroll)\x1b[K",
+            ansi_term::Style::new().bold().paint(&range.name),
+            range.start,
+            range.end
+        );
+
+        let start = range.start + self.offset;
+        let end = (start + BYTES_PER_ROW * ROWS_PER_PAGE).min(range.end);
+        for row_start in (start..end).step_by(BYTES_PER_ROW) {
+            let row_end = (row_start + BYTES_PER_ROW).min(end);
+            let region = regions
+                .iter()
+                .find(|region| region.start <= row_start && row_start < region.end)
+                .map_or("", |region| region.name.as_str());
+            let _ = write!(out, "{:05x} {:<12}", row_start, region);
+
+            for address in row_start..(row_start + BYTES_PER_ROW) {
+                if address >= row_end {
+                    out.push_str("   ");
+                    continue;
+                }
+                let byte = memory[address];
+                if self.previous.get(address).is_some_and(|&prev| prev != byte) {
+                    let _ = write!(
+                        out,
+                        " {}",
+                        ansi_term::Colour::Yellow
+                            .bold()
+                            .paint(format!("{:02x}", byte))
+                    );
+                } else {
+                    let _ = write!(out, " {:02x}", byte);
+                }
+            }
+
+            out.push_str("  ");
+            for &byte in &memory[row_start..row_end] {
+                out.push(if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                });
+            }
+            out.push_str("\x1b[K\n");
+        }
+        out.push_str("\x1b[J");
+        print!("{}", out);
+        let _ = std::io::stdout().flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(s: &str) -> Result<(usize, usize)> {
+        let range: MemoryRange = s.parse()?;
+        Ok((range.start, range.end))
+    }
+
+    #[test]
+    fn parses_region_names_ignoring_case() {
+        let range: MemoryRange = "user_mem".parse().unwrap();
+        assert_eq!(
+            (range.name.as_str(), range.start, range.end),
+            ("USER_MEM", 0x14000, MEMORY_SIZE)
+        );
+    }
+
+    #[test]
+    fn parses_hex_ranges() {
+        assert_eq!(range("13000-13400").unwrap(), (0x13000, 0x13400));
+        assert_eq!(range("0x78-0x100").unwrap(), (0x78, 0x100));
+        assert_eq!(range("14000+100").unwrap(), (0x14000, 0x14100));
+        assert_eq!(range("3ff00+100").unwrap(), (0x3ff00, MEMORY_SIZE));
+    }
+
+    #[test]
+    fn rejects_invalid_ranges() {
+        assert!(range("framebuf").is_err());
+        assert!(range("100").is_err());
+        assert!(range("xyz-200").is_err());
+        assert!(range("200-100").is_err());
+        assert!(range("100+0").is_err());
+        assert!(range("3ff00+101").is_err());
+        assert!(range("100+ffffffffffffffff").is_err());
+    }
+}
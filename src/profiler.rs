@@ -0,0 +1,109 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::Timeouts;
+
+const FRAME_BUDGET: Duration = Duration::from_micros(16667);
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Min/avg/max of the measured durations of one part of the cart.
+#[derive(Default, Clone, Copy)]
+pub(crate) struct TimingStats {
+    count: u32,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl TimingStats {
+    pub fn add(&mut self, duration: Duration) {
+        if self.count == 0 {
+            self.min = duration;
+            self.max = duration;
+        } else {
+            self.min = self.min.min(duration);
+            self.max = self.max.max(duration);
+        }
+        self.count += 1;
+        self.total += duration;
+    }
+
+    fn avg(&self) -> Duration {
+        self.total.checked_div(self.count).unwrap_or_default()
+    }
+
+    /// Formats the stats, including the margin to the timeout when timeouts are enabled.
+    fn format(&self, name: &str, budget: Duration, deadline: Option<Duration>) -> String {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let mut line = format!(
+            "{:<9} min {:6.2}ms  avg {:6.2}ms  max {:6.2}ms  {:5.1}% of budget",
+            name,
+            ms(self.min),
+            ms(self.avg()),
+            ms(self.max),
+            self.total.as_secs_f64() / budget.as_secs_f64() * 100.0,
+        );
+        if let Some(deadline) = deadline {
+            line += &format!(
+                "  timeout margin {:5.1}%",
+                (1.0 - self.max.as_secs_f64() / deadline.as_secs_f64()).max(0.0) * 100.0
+            );
+        }
+        line
+    }
+}
+
+/// Collects the time spent in `upd`, `endFrame` and the `snd` calls of the audio
+/// callback and prints a summary every second.
+pub(crate) struct Profiler {
+    update: TimingStats,
+    end_frame: TimingStats,
+    sound: Arc<Mutex<TimingStats>>,
+    frame_deadline: Option<Duration>,
+    sound_deadline: Option<Duration>,
+    last_report: Instant,
+}
+
+impl Profiler {
+    /// Without `timeouts`, no timeout margin is shown.
+    pub fn new(timeouts: Option<&Timeouts>) -> Profiler {
+        Profiler {
+            update: TimingStats::default(),
+            end_frame: TimingStats::default(),
+            sound: Arc::new(Mutex::new(TimingStats::default())),
+            frame_deadline: timeouts.map(|timeouts| timeouts.update),
+            sound_deadline: timeouts.map(|timeouts| timeouts.sound),
+            last_report: Instant::now(),
+        }
+    }
+
+    /// The stats the audio callback adds its timings to.
+    pub fn sound_stats(&self) -> Arc<Mutex<TimingStats>> {
+        self.sound.clone()
+    }
+
+    pub fn add_frame(&mut self, update: Duration, end_frame: Duration) {
+        self.update.add(update);
+        self.end_frame.add(end_frame);
+
+        let elapsed = self.last_report.elapsed();
+        if elapsed < REPORT_INTERVAL {
+            return;
+        }
+
+        let frames_budget = FRAME_BUDGET * self.update.count;
+        let sound = std::mem::take(&mut *self.sound.lock().unwrap());
+        println!(
+            "{}\n{}\n{}",
+            self.update
+                .format("upd", frames_budget, self.frame_deadline),
+            self.end_frame
+                .format("endFrame", frames_budget, self.frame_deadline),
+            sound.format("snd", elapsed, self.sound_deadline)
+        );
+
+        self.update = TimingStats::default();
+        self.end_frame = TimingStats::default();
+        self.last_report = Instant::now();
+    }
+}
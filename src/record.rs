@@ -0,0 +1,71 @@
+use anyhow::Result;
+use std::fs::File;
+use std::io::{prelude::*, BufWriter};
+use std::path::Path;
+
+/// Writes 320x240 60fps RGB frames as an uncompressed YUV4MPEG2 (.y4m) video.
+pub struct VideoWriter {
+    out: BufWriter<File>,
+    planes: Vec<u8>,
+}
+
+impl VideoWriter {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<VideoWriter> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "YUV4MPEG2 W320 H240 F60:1 Ip A1:1 C444")?;
+        Ok(VideoWriter {
+            out,
+            planes: vec![0; 320 * 240 * 3],
+        })
+    }
+
+    /// Writes one frame of 24bpp RGB pixels.
+    pub fn write_frame(&mut self, rgb: &[u8]) -> Result<()> {
+        const PLANE_SIZE: usize = 320 * 240;
+        for (i, pixel) in rgb.chunks_exact(3).enumerate() {
+            let r = pixel[0] as i32;
+            let g = pixel[1] as i32;
+            let b = pixel[2] as i32;
+            // BT.601 limited range, which is what most tools assume for y4m files
+            self.planes[i] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+            self.planes[PLANE_SIZE + i] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+            self.planes[PLANE_SIZE * 2 + i] =
+                (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+        }
+        self.out.write_all(b"FRAME\n")?;
+        self.out.write_all(&self.planes)?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes interleaved stereo 44100Hz samples as a 16bit PCM .wav file.
+pub fn write_wav<P: AsRef<Path>>(path: P, samples: &[f32]) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+
+    let data_size = samples.len() as u32 * 2;
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_size).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?; // PCM
+    out.write_all(&2u16.to_le_bytes())?; // channels
+    out.write_all(&44100u32.to_le_bytes())?;
+    out.write_all(&(44100u32 * 4).to_le_bytes())?; // bytes per second
+    out.write_all(&4u16.to_le_bytes())?; // bytes per sample frame
+    out.write_all(&16u16.to_le_bytes())?; // bits per sample
+    out.write_all(b"data")?;
+    out.write_all(&data_size.to_le_bytes())?;
+
+    for &sample in samples {
+        let sample = (sample.max(-1.0).min(1.0) * 32767.0) as i16;
+        out.write_all(&sample.to_le_bytes())?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,281 @@
+use anyhow::{anyhow, Result};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wasmtime::{Engine, Module};
+
+use crate::run_native::{
+    create_engine, frame_time, SaveState, SoundInstance, Timeouts, UW8Instance,
+};
+use crate::sampling_profiler::{EpochTicker, Sampler, SharedSampler};
+use crate::{InputFrame, InputReplay, MouseState};
+
+/// Number of stereo samples generated per frame at 44100Hz and 60fps.
+const SAMPLES_PER_FRAME: u32 = 44100 / 60;
+
+/// A runtime without window or audio output.
+///
+/// Carts are instantiated exactly like in the native runtime, but time is driven by a
+/// virtual clock advancing by 1/60s per frame and the resulting framebuffer and palette
+/// are kept in memory for inspection. When audio is enabled, the sound output for each
+/// frame is rendered sample-exactly alongside it.
+pub struct Headless {
+    engine: Engine,
+    loader_module: Module,
+    module_data: Option<Vec<u8>>,
+    timeouts: Timeouts,
+    instance: Option<UW8Instance>,
+    input: InputFrame,
+    frame: u32,
+    framebuffer: Vec<u8>,
+    palette: Vec<u8>,
+    enable_audio: bool,
+    save_states: bool,
+    sound: Option<SoundInstance>,
+    audio: Vec<f32>,
+    input_replay: Option<InputReplay>,
+    profile_interval: Option<Duration>,
+    sampler: SharedSampler,
+    epoch_ticker: Option<EpochTicker>,
+}
+
+impl Headless {
+    pub fn new(timeouts: Option<Timeouts>) -> Result<Headless> {
+        let (engine, loader_module) = create_engine(timeouts.is_some())?;
+
+        Ok(Headless {
+            engine,
+            loader_module,
+            module_data: None,
+            timeouts: timeouts.unwrap_or_default(),
+            instance: None,
+            input: InputFrame::default(),
+            frame: 0,
+            framebuffer: vec![0; 320 * 240],
+            palette: vec![0; 1024],
+            enable_audio: false,
+            save_states: false,
+            sound: None,
+            audio: Vec::new(),
+            input_replay: None,
+            profile_interval: None,
+            sampler: Arc::new(Mutex::new(None)),
+            epoch_ticker: None,
+        })
+    }
+
+    /// Renders the sound output of the cart, takes effect on the next load.
+    pub fn enable_audio(&mut self) {
+        self.enable_audio = true;
+    }
+
+    /// Allows `save_state` and `load_state`, takes effect on the next load. This exports
+    /// the internal globals of the cart.
+    pub fn enable_save_states(&mut self) {
+        self.save_states = true;
+    }
+
+    /// Replays previously recorded input, overriding `set_gamepads` until the
+    /// recording is exhausted.
+    pub fn replay_input(&mut self, replay: InputReplay) {
+        self.input_replay = Some(replay);
+    }
+
+    /// Samples the call stack of the cart every `interval`, takes effect on the next
+    /// load. Needs a timeout, which is checked by the sampler while profiling.
+    pub fn enable_profiling(&mut self, interval: Duration) {
+        self.profile_interval = Some(interval);
+    }
+
+    /// Writes the samples collected so far as a Firefox profiler json file and stops
+    /// profiling.
+    pub fn finish_profile<W: Write>(&mut self, out: W) -> Result<()> {
+        let sampler = self
+            .sampler
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| anyhow!("Profiling not enabled"))?;
+        self.epoch_ticker = None;
+        sampler.finish(out)
+    }
+
+    /// Sets the gamepad state used for all following frames.
+    pub fn set_gamepads(&mut self, gamepads: [u8; 4]) {
+        self.input.gamepads = gamepads;
+    }
+
+    /// Sets the mouse state used for all following frames.
+    pub fn set_mouse(&mut self, mouse: MouseState) {
+        self.input.mouse = mouse;
+    }
+
+    /// Restarts the currently loaded cart from scratch.
+    pub fn reset(&mut self) -> Result<()> {
+        if let Some(module_data) = self.module_data.clone() {
+            super::Runtime::load(self, &module_data)?;
+        }
+        Ok(())
+    }
+
+    /// Takes a snapshot of the running cart.
+    pub fn save_state(&mut self) -> Result<SaveState> {
+        let instance = self
+            .instance
+            .as_mut()
+            .ok_or_else(|| anyhow!("No cart running"))?;
+        let mut state = instance.save_state()?;
+        if let Some(ref mut sound) = self.sound {
+            state.sound = Some(sound.save_state());
+        }
+        Ok(state)
+    }
+
+    /// Restores a snapshot taken with `save_state`, reloading the cart first if it
+    /// has stopped because of a runtime error.
+    pub fn load_state(&mut self, state: &SaveState) -> Result<()> {
+        if self.instance.is_none() {
+            self.reset()?;
+        }
+        let instance = self
+            .instance
+            .as_mut()
+            .ok_or_else(|| anyhow!("No cart loaded"))?;
+        instance.load_state(state)?;
+        self.frame = state.frame_counter;
+        if let (Some(sound), Some(sound_state)) = (&mut self.sound, &state.sound) {
+            sound.load_state(sound_state)?;
+        }
+        Ok(())
+    }
+
+    /// Number of frames run since the cart was loaded.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// The virtual time in milliseconds for the next frame.
+    pub fn time(&self) -> i32 {
+        frame_time(self.frame)
+    }
+
+    /// The 320x240 8bpp framebuffer at the end of the last frame.
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.framebuffer
+    }
+
+    /// The 256 entry 32bpp palette at the end of the last frame.
+    pub fn palette(&self) -> &[u8] {
+        &self.palette
+    }
+
+    /// The last frame resolved through the palette as 24bpp RGB.
+    pub fn rgb_frame(&self) -> Vec<u8> {
+        let mut rgb = Vec::with_capacity(320 * 240 * 3);
+        for &color_index in &self.framebuffer {
+            let offset = color_index as usize * 4;
+            rgb.extend_from_slice(&self.palette[offset..offset + 3]);
+        }
+        rgb
+    }
+
+    /// The interleaved stereo 44100Hz samples generated during the last frame.
+    pub fn audio(&self) -> &[f32] {
+        &self.audio
+    }
+
+    /// The full memory of the running cart, if one is loaded.
+    pub fn memory(&self) -> Option<&[u8]> {
+        self.instance.as_ref().map(|instance| instance.memory())
+    }
+}
+
+impl super::Runtime for Headless {
+    fn is_open(&self) -> bool {
+        true
+    }
+
+    fn load(&mut self, module_data: &[u8]) -> Result<()> {
+        self.instance = None;
+        self.sound = None;
+        self.frame = 0;
+
+        if let Some(ref replay) = self.input_replay {
+            replay.check_cart(module_data)?;
+        }
+
+        let mut instance = UW8Instance::new(
+            &self.engine,
+            &self.loader_module,
+            module_data,
+            self.timeouts,
+            self.save_states,
+        )?;
+        if let Some(interval) = self.profile_interval {
+            // samples are only resolved for the modules of the first instance
+            self.sampler.lock().unwrap().get_or_insert_with(|| {
+                Sampler::new(interval, &instance.platform_module, &instance.module)
+            });
+            instance.set_sampler(self.sampler.clone());
+            if self.epoch_ticker.is_none() {
+                self.epoch_ticker = Some(EpochTicker::new(&self.engine, interval));
+            }
+        }
+        instance.start()?;
+        if self.enable_audio {
+            self.sound = Some(SoundInstance::new(
+                &self.engine,
+                &instance.platform_module,
+                &instance.module,
+                self.timeouts.sound,
+            )?);
+        }
+
+        self.instance = Some(instance);
+        self.module_data = Some(module_data.into());
+        Ok(())
+    }
+
+    fn run_frame(&mut self) -> Result<()> {
+        if let Some(ref mut replay) = self.input_replay {
+            if let Some(input) = replay.next_frame() {
+                let reset = input.reset;
+                self.input = input;
+                if reset {
+                    self.reset()?;
+                }
+            }
+        }
+
+        let mut result = Ok(());
+        if let Some(mut instance) = self.instance.take() {
+            let time = frame_time(self.frame);
+            result = instance.run_frame(time, &self.input);
+            // typed text is only delivered once
+            self.input.text.clear();
+
+            self.audio.clear();
+            if let Some(ref mut sound) = self.sound {
+                sound.reset_deadline();
+                sound.set_registers(&instance.memory()[80..112]);
+                sound.set_time(time);
+                let first_sample = self.frame.wrapping_mul(SAMPLES_PER_FRAME * 2);
+                for i in 0..SAMPLES_PER_FRAME * 2 {
+                    self.audio
+                        .push(sound.sample(first_sample.wrapping_add(i) as i32));
+                }
+            }
+
+            self.frame = self.frame.wrapping_add(1);
+
+            self.framebuffer.copy_from_slice(instance.framebuffer());
+            self.palette.copy_from_slice(instance.palette());
+
+            if result.is_ok() {
+                self.instance = Some(instance);
+            }
+        }
+
+        result
+    }
+}
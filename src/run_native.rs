@@ -1,3 +1,4 @@
+use std::path::Path;
 use std::sync::{mpsc, Arc, Mutex};
 use std::time::Duration;
 use std::{thread, time::Instant};
@@ -7,9 +8,24 @@ use cpal::traits::*;
 use rubato::Resampler;
 use uw8_window::{Window, WindowConfig};
 use wasmtime::{
-    Engine, Func, GlobalType, Memory, MemoryType, Module, Mutability, Store, TypedFunc, ValType,
+    Engine, Func, GlobalType, Instance, Memory, MemoryType, Module, Mutability, Store, TypedFunc,
+    UpdateDeadline, Val, ValType,
 };
 
+use crate::capture::Capture;
+use crate::error_screen::ErrorScreen;
+use crate::fnv::fnv1a;
+use crate::profiler::{Profiler, TimingStats};
+use crate::sampling_profiler::SharedSampler;
+use crate::trap_report::{LogOutput, TrapReport};
+use crate::{InputFrame, InputRecorder, InputReplay, MemoryInspector, MemoryRange, MouseState};
+
+const TIME_SCALES: [f64; 4] = [0.25, 0.5, 1.0, 2.0];
+const DEFAULT_TIME_SCALE: usize = 2;
+
+/// The interval at which the watchdog thread increments the epoch.
+const EPOCH_INTERVAL: Duration = Duration::from_millis(5);
+
 pub struct MicroW8 {
     window: Window,
     stream: Option<cpal::Stream>,
@@ -17,19 +33,53 @@ pub struct MicroW8 {
     loader_module: Module,
     disable_audio: bool,
     module_data: Option<Vec<u8>>,
-    timeout: u32,
+    timeouts: Timeouts,
+    timeouts_enabled: bool,
     instance: Option<UW8Instance>,
+    start_time: Instant,
+    time_base: f64,
+    time_scale: usize,
+    paused: bool,
+    sound_tx: Option<mpsc::SyncSender<SoundMessage>>,
+    sound_playback: Option<Arc<Mutex<SoundPlayback>>>,
+    input_recorder: Option<InputRecorder>,
+    input_replay: Option<InputReplay>,
+    saved_state: Option<SaveState>,
+    save_states: bool,
+    hot_reload: bool,
+    hot_reload_screen: bool,
+    inspector: MemoryInspector,
+    profiler: Option<Profiler>,
+    error_screen: Option<ErrorScreen>,
+    capture: Option<Capture>,
+    cart_name: Option<String>,
+    /// Characters typed while paused, passed to the cart on the next frame it runs.
+    pending_text: Vec<u8>,
 }
 
-struct UW8Instance {
+pub(crate) struct UW8Instance {
     store: Store<()>,
     memory: Memory,
     end_frame: TypedFunc<(), ()>,
     update: Option<TypedFunc<(), ()>>,
-    start_time: Instant,
+    start: Option<TypedFunc<(), ()>>,
     frame_counter: u32,
+    update_time: Duration,
+    end_frame_time: Duration,
+    sampler: Option<SharedSampler>,
+    timeouts: Timeouts,
+    log: Arc<Mutex<LogOutput>>,
     watchdog: Arc<Mutex<UW8WatchDog>>,
-    sound_tx: Option<mpsc::SyncSender<RegisterUpdate>>,
+    platform_instance: Instance,
+    instance: Instance,
+    pub platform_module: Module,
+    pub module: Module,
+    /// Hash of the cart data, save states can only be loaded into the same cart.
+    cart_hash: u64,
+    /// Whether the internal globals of the cart were exported for save states.
+    save_states: bool,
+    /// Whether the cart has read typed characters from the keyboard buffer.
+    reads_text: bool,
 }
 
 impl Drop for UW8Instance {
@@ -45,81 +95,225 @@ struct UW8WatchDog {
     stop: bool,
 }
 
-impl MicroW8 {
-    pub fn new(timeout: Option<u32>, window_config: WindowConfig) -> Result<MicroW8> {
-        let mut config = wasmtime::Config::new();
-        config.cranelift_opt_level(wasmtime::OptLevel::Speed);
-        if timeout.is_some() {
-            config.epoch_interruption(true);
-        }
-        let engine = wasmtime::Engine::new(&config)?;
+/// A snapshot of a running cart: its memory, the mutable globals of the cart and
+/// platform instances, the frame counter and the state of the sound instance.
+#[derive(Clone)]
+pub struct SaveState {
+    cart_hash: u64,
+    instance: InstanceState,
+    pub(crate) frame_counter: u32,
+    pub(crate) sound: Option<SoundState>,
+}
 
-        let loader_module =
-            wasmtime::Module::new(&engine, include_bytes!("../platform/bin/loader.wasm"))?;
+impl SaveState {
+    /// The time in milliseconds of the last frame before the snapshot.
+    pub fn time(&self) -> i32 {
+        i32::from_le_bytes(self.instance.memory[64..68].try_into().unwrap())
+    }
+}
 
-        let window = Window::new(window_config)?;
+#[derive(Clone)]
+pub(crate) struct SoundState {
+    instance: InstanceState,
+    sample_index: i32,
+    current_time: i32,
+}
 
-        Ok(MicroW8 {
-            window,
-            stream: None,
-            engine,
-            loader_module,
-            disable_audio: false,
-            module_data: None,
-            timeout: timeout.unwrap_or(0),
-            instance: None,
+#[derive(Clone)]
+pub(crate) struct InstanceState {
+    memory: Vec<u8>,
+    platform_globals: Vec<(String, Val)>,
+    cart_globals: Vec<(String, Val)>,
+}
+
+impl InstanceState {
+    fn save(
+        store: &mut Store<()>,
+        memory: &Memory,
+        platform_instance: &Instance,
+        instance: &Instance,
+    ) -> InstanceState {
+        InstanceState {
+            memory: memory.data(&*store).to_vec(),
+            platform_globals: save_globals(store, platform_instance),
+            cart_globals: save_globals(store, instance),
+        }
+    }
+
+    fn load(
+        &self,
+        store: &mut Store<()>,
+        memory: &Memory,
+        platform_instance: &Instance,
+        instance: &Instance,
+    ) -> Result<()> {
+        memory.data_mut(&mut *store).copy_from_slice(&self.memory);
+        load_globals(store, platform_instance, &self.platform_globals)?;
+        load_globals(store, instance, &self.cart_globals)?;
+        Ok(())
+    }
+}
+
+/// Reads all exported mutable globals. `uw8_tool::export_globals` makes sure that
+/// this includes all internal ones.
+fn save_globals(store: &mut Store<()>, instance: &Instance) -> Vec<(String, Val)> {
+    let globals: Vec<(String, wasmtime::Global)> = instance
+        .exports(&mut *store)
+        .filter_map(|e| {
+            let name = e.name().to_owned();
+            e.into_global().map(|global| (name, global))
         })
+        .collect();
+    globals
+        .into_iter()
+        .filter(|(_, global)| global.ty(&*store).mutability() == Mutability::Var)
+        .map(|(name, global)| (name, global.get(&mut *store)))
+        .collect()
+}
+
+fn load_globals(
+    store: &mut Store<()>,
+    instance: &Instance,
+    globals: &[(String, Val)],
+) -> Result<()> {
+    for (name, value) in globals {
+        if let Some(global) = instance.get_global(&mut *store, name) {
+            global.set(&mut *store, value.clone())?;
+        }
     }
+    Ok(())
+}
 
-    pub fn disable_audio(&mut self) {
-        self.disable_audio = true;
+/// Read and write position of the keyboard ring buffer, one byte each.
+const KEYBOARD_POSITIONS: usize = 0x13c00;
+/// The 256 byte keyboard ring buffer.
+const KEYBOARD_BUFFER: usize = 0x13d00;
+
+/// Appends typed characters to the keyboard ring buffer, dropping them when it is full.
+fn push_text(mem: &mut [u8], text: &[u8]) {
+    let read = mem[KEYBOARD_POSITIONS];
+    let mut write = mem[KEYBOARD_POSITIONS + 1];
+    for &c in text {
+        if write.wrapping_add(1) == read {
+            break;
+        }
+        mem[KEYBOARD_BUFFER + write as usize] = c;
+        write = write.wrapping_add(1);
     }
+    mem[KEYBOARD_POSITIONS + 1] = write;
 }
 
-impl super::Runtime for MicroW8 {
-    fn is_open(&self) -> bool {
-        self.window.is_open()
+/// Converts a typed character to the MicroW8 character set: newline (10), backspace
+/// (8) and the printable latin-1 characters.
+pub(crate) fn to_uw8_char(c: char) -> Option<u8> {
+    match c {
+        '\r' | '\n' => Some(10),
+        '\u{8}' | '\u{7f}' => Some(8),
+        ' '..='~' | '\u{a0}'..='\u{ff}' => Some(c as u8),
+        _ => None,
     }
+}
 
-    fn load(&mut self, module_data: &[u8]) -> Result<()> {
-        self.stream = None;
-        self.instance = None;
+/// The time in milliseconds of the given frame when running at a fixed 60fps.
+pub(crate) fn frame_time(frame: u32) -> i32 {
+    (frame as u64 * 1000 / 60) as i32
+}
+
+/// Time limits for the calls into the cart. A call running longer is interrupted and
+/// reported as a hang.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeouts {
+    /// Limit for instantiating the cart and running `start`.
+    pub start: Duration,
+    /// Limit for `upd` and `endFrame` together.
+    pub update: Duration,
+    /// Limit for each block of samples generated by `snd`.
+    pub sound: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Timeouts {
+        Timeouts {
+            start: Duration::from_millis(1000),
+            update: Duration::from_millis(500),
+            sound: Duration::from_millis(500),
+        }
+    }
+}
+
+/// The epoch deadline guaranteeing at least `timeout` before the interrupt.
+fn epoch_deadline(timeout: Duration) -> u64 {
+    timeout.as_nanos().div_ceil(EPOCH_INTERVAL.as_nanos()) as u64 + 1
+}
+
+pub(crate) fn create_engine(enable_timeouts: bool) -> Result<(Engine, Module)> {
+    let mut config = wasmtime::Config::new();
+    config.cranelift_opt_level(wasmtime::OptLevel::Speed);
+    if enable_timeouts {
+        config.epoch_interruption(true);
+    }
+    let engine = wasmtime::Engine::new(&config)?;
 
-        let mut store = wasmtime::Store::new(&self.engine, ());
-        store.set_epoch_deadline(60);
+    let loader_module =
+        wasmtime::Module::new(&engine, include_bytes!("../platform/bin/loader.wasm"))?;
+
+    Ok((engine, loader_module))
+}
+
+impl UW8Instance {
+    pub fn new(
+        engine: &Engine,
+        loader_module: &Module,
+        module_data: &[u8],
+        timeouts: Timeouts,
+        save_states: bool,
+    ) -> Result<UW8Instance> {
+        let mut store = wasmtime::Store::new(engine, ());
+        store.set_epoch_deadline(epoch_deadline(timeouts.start));
 
         let memory = wasmtime::Memory::new(&mut store, MemoryType::new(4, Some(4)))?;
 
-        let mut linker = wasmtime::Linker::new(&self.engine);
+        let mut linker = wasmtime::Linker::new(engine);
         linker.define(&store, "env", "memory", memory)?;
 
-        let loader_instance = linker.instantiate(&mut store, &self.loader_module)?;
+        let loader_instance = linker.instantiate(&mut store, loader_module)?;
         let load_uw8 = loader_instance.get_typed_func::<i32, i32>(&mut store, "load_uw8")?;
 
         let platform_data = include_bytes!("../platform/bin/platform.uw8");
         memory.data_mut(&mut store)[..platform_data.len()].copy_from_slice(platform_data);
         let platform_length =
             load_uw8.call(&mut store, platform_data.len() as i32)? as u32 as usize;
-        let platform_module =
-            wasmtime::Module::new(&self.engine, &memory.data(&store)[..platform_length])?;
+        let platform_module = wasmtime::Module::new(
+            engine,
+            uw8_tool::export_globals(&uw8_tool::name_exported_functions(
+                &memory.data(&store)[..platform_length],
+            )?)?,
+        )?;
 
         memory.data_mut(&mut store)[..module_data.len()].copy_from_slice(module_data);
         let module_length = load_uw8.call(&mut store, module_data.len() as i32)? as u32 as usize;
-        let module = wasmtime::Module::new(&self.engine, &memory.data(&store)[..module_length])?;
+        let loaded_module = &memory.data(&store)[..module_length];
+        // only rewrite the cart when needed, so it otherwise runs exactly as given
+        let module = if save_states {
+            wasmtime::Module::new(engine, uw8_tool::export_globals(loaded_module)?)?
+        } else {
+            wasmtime::Module::new(engine, loaded_module)?
+        };
 
-        add_native_functions(&mut linker, &mut store)?;
+        let log = Arc::new(Mutex::new(LogOutput::default()));
+        add_native_functions(&mut linker, &mut store, log.clone())?;
 
         let platform_instance = instantiate_platform(&mut linker, &mut store, &platform_module)?;
 
         let watchdog = Arc::new(Mutex::new(UW8WatchDog {
-            engine: self.engine.clone(),
+            engine: engine.clone(),
             stop: false,
         }));
 
         {
             let watchdog = watchdog.clone();
             thread::spawn(move || loop {
-                thread::sleep(Duration::from_millis(17));
+                thread::sleep(EPOCH_INTERVAL);
                 if let Ok(watchdog) = watchdog.lock() {
                     if watchdog.stop {
                         break;
@@ -135,95 +329,712 @@ impl super::Runtime for MicroW8 {
         let end_frame = platform_instance.get_typed_func::<(), ()>(&mut store, "endFrame")?;
         let update = instance.get_typed_func::<(), ()>(&mut store, "upd").ok();
 
-        if let Some(start) = instance.get_typed_func::<(), ()>(&mut store, "start").ok() {
-            start.call(&mut store, ())?;
+        let start = instance.get_typed_func::<(), ()>(&mut store, "start").ok();
+
+        Ok(UW8Instance {
+            store,
+            memory,
+            end_frame,
+            update,
+            start,
+            frame_counter: 0,
+            update_time: Duration::ZERO,
+            end_frame_time: Duration::ZERO,
+            sampler: None,
+            timeouts,
+            log,
+            watchdog,
+            platform_instance,
+            instance,
+            platform_module,
+            module,
+            cart_hash: fnv1a(module_data),
+            save_states,
+            reads_text: false,
+        })
+    }
+
+    /// Calls the `start` function of the cart, if it exports one.
+    pub fn start(&mut self) -> Result<()> {
+        self.begin_call(self.timeouts.start);
+        if let Some(ref start) = self.start {
+            if let Err(err) = start.call(&mut self.store, ()) {
+                return Err(self.trap_report(err, None, "start", self.timeouts.start));
+            }
         }
+        Ok(())
+    }
+
+    fn trap_report(
+        &self,
+        err: anyhow::Error,
+        frame: Option<u32>,
+        export: &'static str,
+        timeout: Duration,
+    ) -> anyhow::Error {
+        anyhow::Error::new(TrapReport::new(
+            &err,
+            frame,
+            export,
+            timeout,
+            &self.platform_module,
+            &self.module,
+            &self.log.lock().unwrap(),
+        ))
+    }
 
-        let (sound_tx, stream) = if self.disable_audio {
-            (None, None)
+    /// Samples the call stack at every epoch increment. The sampler then takes over
+    /// the timeout check from the epoch deadline.
+    pub fn set_sampler(&mut self, sampler: SharedSampler) {
+        let callback_sampler = sampler.clone();
+        self.store.epoch_deadline_callback(move |store| {
+            if let Some(ref mut sampler) = *callback_sampler.lock().unwrap() {
+                sampler.sample(&store)?;
+            }
+            Ok(UpdateDeadline::Continue(1))
+        });
+        self.sampler = Some(sampler);
+    }
+
+    fn begin_call(&mut self, timeout: Duration) {
+        if let Some(ref sampler) = self.sampler {
+            if let Some(ref mut sampler) = *sampler.lock().unwrap() {
+                sampler.begin_call(timeout);
+            }
+            self.store.set_epoch_deadline(1);
         } else {
-            match init_sound(&self.engine, &platform_module, &module) {
+            self.store.set_epoch_deadline(epoch_deadline(timeout));
+        }
+    }
+
+    /// Continues where `old` left off instead of calling `start`: copies the user
+    /// memory from 0x14000 onward, the frame counter and optionally the framebuffer and
+    /// palette over from the old instance.
+    pub fn hot_reload(&mut self, old: &UW8Instance, keep_screen: bool) {
+        let old_mem = old.memory();
+        let mem = self.memory.data_mut(&mut self.store);
+        mem[0x14000..].copy_from_slice(&old_mem[0x14000..]);
+        if keep_screen {
+            mem[120..(120 + 320 * 240)].copy_from_slice(&old_mem[120..(120 + 320 * 240)]);
+            mem[0x13000..0x13400].copy_from_slice(&old_mem[0x13000..0x13400]);
+        }
+        self.frame_counter = old.frame_counter;
+        self.reads_text = old.reads_text;
+    }
+
+    pub fn save_state(&mut self) -> Result<SaveState> {
+        if !self.save_states {
+            bail!("Save states are not enabled");
+        }
+        Ok(SaveState {
+            cart_hash: self.cart_hash,
+            instance: InstanceState::save(
+                &mut self.store,
+                &self.memory,
+                &self.platform_instance,
+                &self.instance,
+            ),
+            frame_counter: self.frame_counter,
+            sound: None,
+        })
+    }
+
+    pub fn load_state(&mut self, state: &SaveState) -> Result<()> {
+        if state.cart_hash != self.cart_hash {
+            bail!("Save state belongs to a different cart");
+        }
+        state.instance.load(
+            &mut self.store,
+            &self.memory,
+            &self.platform_instance,
+            &self.instance,
+        )?;
+        self.frame_counter = state.frame_counter;
+        Ok(())
+    }
+
+    /// Runs `upd` and `endFrame` for one frame with the given time and gamepad state.
+    ///
+    /// `endFrame` is called even if `upd` traps, so the framebuffer always shows the
+    /// state at the end of the frame.
+    pub fn run_frame(&mut self, time: i32, input: &InputFrame) -> Result<()> {
+        {
+            let mem = self.memory.data_mut(&mut self.store);
+            mem[64..68].copy_from_slice(&time.to_le_bytes());
+            mem[68..72].copy_from_slice(&input.gamepads);
+            mem[112..117].copy_from_slice(&input.mouse.to_bytes());
+            push_text(mem, &input.text);
+            mem[72..76].copy_from_slice(&self.frame_counter.to_le_bytes());
+        }
+        let read_position = self.memory()[KEYBOARD_POSITIONS];
+
+        let frame = self.frame_counter;
+        self.frame_counter = self.frame_counter.wrapping_add(1);
+
+        let mut result = Ok(());
+        let timeout = self.timeouts.update;
+        self.begin_call(timeout);
+        let start = Instant::now();
+        if let Some(ref update) = self.update {
+            if let Err(err) = update.call(&mut self.store, ()) {
+                result = Err(self.trap_report(err, Some(frame), "upd", timeout));
+            }
+        }
+        let update_end = Instant::now();
+        if let Err(err) = self.end_frame.call(&mut self.store, ()) {
+            return Err(self.trap_report(err, Some(frame), "endFrame", timeout));
+        }
+        self.update_time = update_end - start;
+        self.end_frame_time = update_end.elapsed();
+        self.reads_text |= self.memory()[KEYBOARD_POSITIONS] != read_position;
+
+        result
+    }
+
+    /// The time spent in `upd` and `endFrame` during the last frame.
+    pub fn frame_timings(&self) -> (Duration, Duration) {
+        (self.update_time, self.end_frame_time)
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        self.memory.data(&self.store)
+    }
+
+    pub fn frame_counter(&self) -> u32 {
+        self.frame_counter
+    }
+
+    /// Whether the cart has read typed characters, so it takes text input.
+    pub fn reads_text(&self) -> bool {
+        self.reads_text
+    }
+
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.memory()[120..(120 + 320 * 240)]
+    }
+
+    pub fn palette(&self) -> &[u8] {
+        &self.memory()[0x13000..0x13400]
+    }
+
+    pub fn font(&self) -> &[u8] {
+        &self.memory()[0x13400..0x13c00]
+    }
+}
+
+impl MicroW8 {
+    pub fn new(timeouts: Option<Timeouts>, window_config: WindowConfig) -> Result<MicroW8> {
+        let (engine, loader_module) = create_engine(timeouts.is_some())?;
+
+        let window = Window::new(window_config)?;
+
+        Ok(MicroW8 {
+            window,
+            stream: None,
+            engine,
+            loader_module,
+            disable_audio: false,
+            module_data: None,
+            timeouts: timeouts.unwrap_or_default(),
+            timeouts_enabled: timeouts.is_some(),
+            instance: None,
+            start_time: Instant::now(),
+            time_base: 0.0,
+            time_scale: DEFAULT_TIME_SCALE,
+            paused: false,
+            sound_tx: None,
+            sound_playback: None,
+            input_recorder: None,
+            input_replay: None,
+            saved_state: None,
+            save_states: false,
+            hot_reload: false,
+            hot_reload_screen: false,
+            inspector: MemoryInspector::default(),
+            profiler: None,
+            error_screen: None,
+            capture: None,
+            cart_name: None,
+            pending_text: Vec::new(),
+        })
+    }
+
+    pub fn disable_audio(&mut self) {
+        self.disable_audio = true;
+    }
+
+    /// Enables saving and loading the state of the cart with F5 and F6, takes effect on
+    /// the next load. This exports the internal globals of the cart.
+    pub fn enable_save_states(&mut self) {
+        self.save_states = true;
+    }
+
+    /// When loading a new version of the running cart, keep the user memory and frame
+    /// counter (and optionally the framebuffer and palette) and skip `start`.
+    /// Resets still restart the cart from scratch.
+    pub fn enable_hot_reload(&mut self, keep_screen: bool) {
+        self.hot_reload = true;
+        self.hot_reload_screen = keep_screen;
+    }
+
+    /// Prints the time spent in `upd`, `endFrame` and `snd` every second.
+    pub fn enable_profiler(&mut self) {
+        self.profiler = Some(Profiler::new(
+            self.timeouts_enabled.then_some(&self.timeouts),
+        ));
+    }
+
+    /// Sets the memory ranges the inspector (toggled with F9) cycles through.
+    pub fn set_inspector_ranges(&mut self, ranges: Vec<MemoryRange>) {
+        self.inspector = MemoryInspector::new(ranges);
+    }
+
+    /// Enables the screenshot (F7) and GIF recording (F8) hotkeys, saving the files
+    /// next to the given cart file.
+    pub fn enable_capture(&mut self, cart_path: &Path) {
+        self.capture = Some(Capture::new(cart_path));
+    }
+
+    /// Sets the name of the cart shown in the window title.
+    pub fn set_cart_name(&mut self, name: &str) {
+        self.cart_name = Some(name.to_string());
+    }
+
+    /// Records the input of every frame, switching to a fixed 60fps time step.
+    pub fn record_input(&mut self, recorder: InputRecorder) {
+        self.input_recorder = Some(recorder);
+    }
+
+    /// Replays previously recorded input with a fixed 60fps time step. Once the
+    /// recording is exhausted, the live input takes over again.
+    pub fn replay_input(&mut self, replay: InputReplay) {
+        self.input_replay = Some(replay);
+    }
+
+    /// Takes a snapshot of the running cart, including the sound state if audio
+    /// is enabled.
+    pub fn save_state(&mut self) -> Result<SaveState> {
+        if !self.save_states {
+            bail!("Save states are not enabled, run with --save-states");
+        }
+        let instance = self
+            .instance
+            .as_mut()
+            .ok_or_else(|| anyhow!("No cart running"))?;
+        let mut state = instance.save_state()?;
+        if let Some(ref playback) = self.sound_playback {
+            let mut playback = playback
+                .lock()
+                .map_err(|_| anyhow!("Sound instance is not available"))?;
+            state.sound = Some(playback.save_state());
+        }
+        Ok(state)
+    }
+
+    /// Restores a snapshot taken with `save_state`. If the cart has stopped because of
+    /// a runtime error, it is reloaded first.
+    pub fn load_state(&mut self, state: &SaveState) -> Result<()> {
+        if self.instance.is_none() {
+            if let Some(module_data) = self.module_data.clone() {
+                self.load_cart(&module_data, false, None)?;
+            }
+        }
+        let instance = self
+            .instance
+            .as_mut()
+            .ok_or_else(|| anyhow!("No cart loaded"))?;
+        instance.load_state(state)?;
+
+        self.start_time = Instant::now();
+        self.time_base = state.time() as f64;
+
+        if let (Some(sound_tx), Some(sound)) = (&self.sound_tx, &state.sound) {
+            let _ = sound_tx.send(SoundMessage::LoadState(sound.clone()));
+        }
+        Ok(())
+    }
+
+    fn fixed_time_step(&self) -> bool {
+        self.input_recorder.is_some() || self.input_replay.is_some()
+    }
+
+    /// The time of the cart in ms, which stands still while paused and runs at
+    /// the selected time scale otherwise.
+    fn cart_time(&self, now: Instant) -> f64 {
+        if self.paused {
+            self.time_base
+        } else {
+            self.time_base
+                + (now - self.start_time).as_secs_f64() * 1000.0 * TIME_SCALES[self.time_scale]
+        }
+    }
+
+    fn set_paused(&mut self, paused: bool) -> Result<()> {
+        let now = Instant::now();
+        self.time_base = self.cart_time(now);
+        self.start_time = now;
+        self.paused = paused;
+        if let Some(ref stream) = self.stream {
+            if paused {
+                stream.pause()?;
+            } else {
+                stream.play()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_time_scale(&mut self, time_scale: usize) {
+        let now = Instant::now();
+        self.time_base = self.cart_time(now);
+        self.start_time = now;
+        self.time_scale = time_scale;
+        println!("Speed: {}x", TIME_SCALES[time_scale]);
+        self.window
+            .notify(format!("speed: {}x", TIME_SCALES[time_scale]));
+    }
+
+    /// Shows the cart name, size and state in the window title.
+    fn update_title(&mut self) {
+        let mut title = String::new();
+        if let Some(ref name) = self.cart_name {
+            title += &format!("{} - ", name);
+        }
+        if let Some(ref module_data) = self.module_data {
+            title += &format!("{} bytes - ", module_data.len());
+        }
+        if self.error_screen.is_some() {
+            title += "error - ";
+        } else if self.paused {
+            title += "paused - ";
+        } else if let Some(fps) = self.window.fps() {
+            title += &format!("{:.0} fps - ", fps);
+        }
+        title += "MicroW8";
+        self.window.set_title(&title);
+    }
+
+    /// Loads a cart, showing `notification` once it is running.
+    fn load_cart(
+        &mut self,
+        module_data: &[u8],
+        hot_reload: bool,
+        notification: Option<&str>,
+    ) -> Result<()> {
+        if let Some(ref replay) = self.input_replay {
+            replay.check_cart(module_data)?;
+        }
+
+        let mut instance = UW8Instance::new(
+            &self.engine,
+            &self.loader_module,
+            module_data,
+            self.timeouts,
+            self.save_states,
+        )?;
+
+        // only carts that load are recorded
+        if let Some(ref mut recorder) = self.input_recorder {
+            recorder.start(module_data)?;
+        }
+
+        // keep the old instance around until now, so a failed hot reload doesn't lose its state
+        self.stream = None;
+        self.sound_tx = None;
+        self.sound_playback = None;
+        let old_instance = self.instance.take();
+        match old_instance {
+            Some(old_instance) if hot_reload => {
+                instance.hot_reload(&old_instance, self.hot_reload_screen);
+            }
+            _ => {
+                drop(old_instance);
+                instance.start()?;
+                self.start_time = Instant::now();
+                self.time_base = 0.0;
+            }
+        }
+
+        let (sound_tx, sound_playback, stream) = if self.disable_audio {
+            (None, None, None)
+        } else {
+            let sound_stats = self
+                .profiler
+                .as_ref()
+                .map(|profiler| profiler.sound_stats());
+            match init_sound(
+                &self.engine,
+                &instance.platform_module,
+                &instance.module,
+                self.timeouts.sound,
+                sound_stats,
+            ) {
                 Ok(sound) => {
-                    sound.stream.play()?;
-                    (Some(sound.tx), Some(sound.stream))
+                    if self.paused {
+                        sound.stream.pause()?;
+                    } else {
+                        sound.stream.play()?;
+                    }
+                    (Some(sound.tx), Some(sound.playback), Some(sound.stream))
                 }
                 Err(err) => {
                     eprintln!("Failed to init sound: {}", err);
-                    (None, None)
+                    (None, None, None)
                 }
             }
         };
 
-        self.instance = Some(UW8Instance {
-            store,
-            memory,
-            end_frame,
-            update,
-            start_time: Instant::now(),
-            frame_counter: 0,
-            watchdog,
-            sound_tx,
-        });
+        if let Some(notification) = notification {
+            self.window.notify(notification);
+        }
+        self.instance = Some(instance);
+        self.error_screen = None;
+        self.sound_tx = sound_tx;
+        self.sound_playback = sound_playback;
         self.stream = stream;
         self.module_data = Some(module_data.into());
         Ok(())
     }
+}
+
+impl super::Runtime for MicroW8 {
+    fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    fn load(&mut self, module_data: &[u8]) -> Result<()> {
+        let notification = if self.module_data.is_none() {
+            None
+        } else if self.hot_reload && self.instance.is_some() {
+            Some("hot reloaded")
+        } else {
+            Some("reloaded")
+        };
+        self.load_cart(module_data, self.hot_reload, notification)
+    }
+
+    fn show_error(&mut self, message: &str) {
+        self.error_screen = Some(ErrorScreen::new("Load error", message));
+        if let Some(ref stream) = self.stream {
+            let _ = stream.pause();
+        }
+    }
 
     fn run_frame(&mut self) -> Result<()> {
-        let input = self.window.begin_frame();
+        self.update_title();
+        let window_input = self.window.begin_frame();
+
+        if window_input.pause {
+            self.set_paused(!self.paused)?;
+            println!("{}", if self.paused { "Paused" } else { "Resumed" });
+            self.window
+                .notify(if self.paused { "paused" } else { "resumed" });
+        }
+        let step = window_input.step;
+        if step && !self.paused {
+            self.set_paused(true)?;
+            println!("Paused");
+            self.window.notify("paused");
+        }
+        if window_input.slower && self.time_scale > 0 {
+            self.set_time_scale(self.time_scale - 1);
+        }
+        if window_input.faster && self.time_scale + 1 < TIME_SCALES.len() {
+            self.set_time_scale(self.time_scale + 1);
+        }
+
+        if window_input.toggle_inspector {
+            self.inspector.toggle();
+        }
+        if window_input.next_inspector_range {
+            self.inspector.next_range();
+        }
+        if window_input.inspector_scroll != 0 {
+            self.inspector.scroll(window_input.inspector_scroll);
+        }
+
+        if window_input.toggle_gif_recording {
+            if let Some(ref mut capture) = self.capture {
+                capture.toggle_gif();
+            }
+        }
+
+        let mut text = std::mem::take(&mut self.pending_text);
+        text.extend(window_input.text.chars().filter_map(to_uw8_char));
+        let mut input = InputFrame {
+            gamepads: window_input.gamepads,
+            mouse: MouseState {
+                x: window_input.mouse_position.0,
+                y: window_input.mouse_position.1,
+                buttons: window_input.mouse_buttons,
+            },
+            text,
+            reset: window_input.reset,
+        };
+
+        if window_input.save_state {
+            match self.save_state() {
+                Ok(state) => {
+                    self.saved_state = Some(state);
+                    println!("State saved");
+                    self.window.notify("state saved");
+                }
+                Err(err) => {
+                    eprintln!("Failed to save state: {}", err);
+                    self.window.notify("failed to save state");
+                }
+            }
+        }
+
+        if window_input.load_state {
+            if let Some(state) = self.saved_state.take() {
+                match self.load_state(&state) {
+                    Ok(()) => {
+                        println!("State loaded");
+                        self.window.notify("state loaded");
+                    }
+                    Err(err) => {
+                        eprintln!("Failed to load state: {}", err);
+                        self.window.notify("failed to load state");
+                    }
+                }
+                self.saved_state = Some(state);
+            }
+        }
+
+        // the cart is kept around for hot reloading, but doesn't run while showing an
+        // error, until the next successful load or reset
+        if let Some(ref error_screen) = self.error_screen {
+            if !input.reset {
+                if let Some(ref mut capture) = self.capture {
+                    capture.frame(
+                        error_screen.framebuffer(),
+                        error_screen.palette(),
+                        window_input.screenshot,
+                        false,
+                    );
+                }
+                self.window.end_frame(
+                    error_screen.framebuffer(),
+                    error_screen.palette(),
+                    Instant::now() + Duration::from_millis(16),
+                );
+                return Ok(());
+            }
+        }
+
+        // a reset while paused shows the first frame of the restarted cart
+        if self.paused && !step && !input.reset {
+            // the keyboard ring buffer only holds 255 characters anyway
+            input.text.truncate(255);
+            self.pending_text = input.text;
+            if let Some(ref instance) = self.instance {
+                self.inspector.draw(instance.memory());
+                if let Some(ref mut capture) = self.capture {
+                    capture.frame(
+                        instance.framebuffer(),
+                        instance.palette(),
+                        window_input.screenshot,
+                        false,
+                    );
+                }
+                self.window.set_font(instance.font());
+                self.window.end_frame(
+                    instance.framebuffer(),
+                    instance.palette(),
+                    Instant::now() + Duration::from_millis(16),
+                );
+            }
+            return Ok(());
+        }
+        if step {
+            self.time_base += 1000.0 / 60.0;
+        }
+
+        if let Some(ref mut replay) = self.input_replay {
+            if let Some(frame) = replay.next_frame() {
+                input = frame;
+                if replay.is_finished() {
+                    println!("Input replay finished, switching to live input");
+                    self.window.notify("replay finished");
+                }
+            }
+        }
+
+        if let Some(ref mut recorder) = self.input_recorder {
+            recorder.record(&input)?;
+        }
 
         if input.reset {
-            if let Some(module_data) = self.module_data.take() {
-                self.load(&module_data)?;
+            if let Some(module_data) = self.module_data.clone() {
+                // unlike the loads from `main`, a failed restart has no other way to show its error
+                if let Err(err) = self.load_cart(&module_data, false, Some("restarted")) {
+                    self.error_screen = Some(ErrorScreen::new("Runtime error", &err.to_string()));
+                    return Err(err);
+                }
             }
         }
 
         let now = Instant::now();
         let mut result = Ok(());
+        let fixed_time_step = self.fixed_time_step();
         if let Some(mut instance) = self.instance.take() {
-            let time = (now - instance.start_time).as_millis() as i32;
-            let next_frame = {
-                let offset = ((time as u32 as i64 * 6) % 100 - 50) / 6;
-                let max = now + Duration::from_millis(17);
-                let next_center = now + Duration::from_millis((16 - offset) as u64);
+            let cart_time = self.cart_time(now);
+            let time = if fixed_time_step {
+                frame_time(instance.frame_counter())
+            } else {
+                cart_time as i32
+            };
+            let next_frame = if self.paused {
+                now + Duration::from_millis(16)
+            } else {
+                // frames are paced along the 60Hz grid of the scaled cart time
+                let time_scale = TIME_SCALES[self.time_scale];
+                let offset = ((cart_time as i32 as u32 as i64 * 6) % 100 - 50) / 6;
+                let max = now + Duration::from_secs_f64(0.017 / time_scale);
+                let next_center =
+                    now + Duration::from_secs_f64((16 - offset) as f64 / 1000.0 / time_scale);
                 next_center.min(max)
             };
 
-            {
-                let mem = instance.memory.data_mut(&mut instance.store);
-                mem[64..68].copy_from_slice(&time.to_le_bytes());
-                mem[68..72].copy_from_slice(&input.gamepads);
-                mem[72..76].copy_from_slice(&instance.frame_counter.to_le_bytes());
+            self.inspector.begin_frame(instance.memory());
+            result = instance.run_frame(time, &input);
+            if let Some(ref mut profiler) = self.profiler {
+                let (update_time, end_frame_time) = instance.frame_timings();
+                profiler.add_frame(update_time, end_frame_time);
             }
+            self.inspector.draw(instance.memory());
 
-            instance.frame_counter = instance.frame_counter.wrapping_add(1);
-
-            instance.store.set_epoch_deadline(self.timeout as u64);
-            if let Some(ref update) = instance.update {
-                if let Err(err) = update.call(&mut instance.store, ()) {
-                    result = Err(err);
-                }
-            }
-            instance.end_frame.call(&mut instance.store, ())?;
-
-            let memory = instance.memory.data(&instance.store);
+            let memory = instance.memory();
 
             let mut sound_regs = [0u8; 32];
             sound_regs.copy_from_slice(&memory[80..112]);
-            if let Some(ref sound_tx) = instance.sound_tx {
-                let _ = sound_tx.send(RegisterUpdate {
+            // the stream doesn't consume any updates while paused
+            if let Some(sound_tx) = self.sound_tx.as_ref().filter(|_| !self.paused) {
+                let _ = sound_tx.send(SoundMessage::Update(RegisterUpdate {
                     time,
                     data: sound_regs,
-                });
+                }));
             }
 
-            let framebuffer_mem = &memory[120..(120 + 320 * 240)];
-            let palette_mem = &memory[0x13000..];
+            if let Some(ref mut capture) = self.capture {
+                capture.frame(
+                    instance.framebuffer(),
+                    instance.palette(),
+                    window_input.screenshot,
+                    true,
+                );
+            }
+            self.window.set_font(instance.font());
+            self.window.set_text_input(instance.reads_text());
             self.window
-                .end_frame(framebuffer_mem, palette_mem, next_frame);
+                .end_frame(instance.framebuffer(), instance.palette(), next_frame);
 
-            if result.is_ok() {
-                self.instance = Some(instance);
+            match result {
+                Ok(()) => self.instance = Some(instance),
+                Err(ref err) => {
+                    self.error_screen = Some(ErrorScreen::new("Runtime error", &err.to_string()))
+                }
             }
         }
 
@@ -235,6 +1046,7 @@ impl super::Runtime for MicroW8 {
 fn add_native_functions(
     linker: &mut wasmtime::Linker<()>,
     store: &mut wasmtime::Store<()>,
+    log: Arc<Mutex<LogOutput>>,
 ) -> Result<()> {
     linker.func_wrap("env", "acos", |v: f32| v.acos())?;
     linker.func_wrap("env", "asin", |v: f32| v.asin())?;
@@ -249,15 +1061,8 @@ fn add_native_functions(
     for i in 10..64 {
         linker.func_wrap("env", &format!("reserved{}", i), || {})?;
     }
-    let log_line = std::sync::Mutex::new(String::new());
     linker.func_wrap("env", "logChar", move |c: i32| {
-        let mut log_line = log_line.lock().unwrap();
-        if c == 10 {
-            println!("{}", log_line);
-            log_line.clear();
-        } else {
-            log_line.push(c as u8 as char);
-        }
+        log.lock().unwrap().push_char(c as u8);
     })?;
     for i in 0..16 {
         let global = wasmtime::Global::new(
@@ -280,12 +1085,9 @@ fn instantiate_platform(
 
     let exports: Vec<(String, Func)> = platform_instance
         .exports(&mut *store)
-        .map(|e| {
-            (
-                e.name().to_owned(),
-                e.into_func()
-                    .expect("platform surely only exports functions"),
-            )
+        .filter_map(|e| {
+            let name = e.name().to_owned();
+            e.into_func().map(|func| (name, func))
         })
         .collect();
     for (name, func) in exports {
@@ -300,31 +1102,167 @@ struct RegisterUpdate {
     data: [u8; 32],
 }
 
+enum SoundMessage {
+    Update(RegisterUpdate),
+    LoadState(SoundState),
+}
+
 struct Uw8Sound {
     stream: cpal::Stream,
-    tx: mpsc::SyncSender<RegisterUpdate>,
+    tx: mpsc::SyncSender<SoundMessage>,
+    playback: Arc<Mutex<SoundPlayback>>,
+}
+
+/// The sound instance and its position in the stream, shared with the audio callback
+/// so a save state can be taken at any time, also while the stream is paused.
+struct SoundPlayback {
+    sound: SoundInstance,
+    sample_index: i32,
+    current_time: i32,
+}
+
+impl SoundPlayback {
+    fn save_state(&mut self) -> SoundState {
+        SoundState {
+            sample_index: self.sample_index,
+            current_time: self.current_time,
+            ..self.sound.save_state()
+        }
+    }
+}
+
+pub(crate) struct SoundInstance {
+    store: Store<()>,
+    memory: Memory,
+    platform_instance: Instance,
+    instance: Instance,
+    snd: TypedFunc<(i32,), f32>,
+    timeout: Duration,
+    platform_module: Module,
+    module: Module,
+    log: Arc<Mutex<LogOutput>>,
+    reported_trap: bool,
+}
+
+impl SoundInstance {
+    pub fn new(
+        engine: &wasmtime::Engine,
+        platform_module: &wasmtime::Module,
+        module: &wasmtime::Module,
+        timeout: Duration,
+    ) -> Result<SoundInstance> {
+        let mut store = wasmtime::Store::new(engine, ());
+        store.set_epoch_deadline(epoch_deadline(timeout));
+
+        let memory = wasmtime::Memory::new(&mut store, MemoryType::new(4, Some(4)))?;
+
+        let mut linker = wasmtime::Linker::new(engine);
+        linker.define(&store, "env", "memory", memory)?;
+        let log = Arc::new(Mutex::new(LogOutput::default()));
+        add_native_functions(&mut linker, &mut store, log.clone())?;
+
+        let platform_instance = instantiate_platform(&mut linker, &mut store, platform_module)?;
+        let instance = linker.instantiate(&mut store, module)?;
+
+        let snd = instance
+            .get_typed_func::<(i32,), f32>(&mut store, "snd")
+            .or_else(|_| platform_instance.get_typed_func::<(i32,), f32>(&mut store, "sndGes"))?;
+
+        Ok(SoundInstance {
+            store,
+            memory,
+            platform_instance,
+            instance,
+            snd,
+            timeout,
+            platform_module: platform_module.clone(),
+            module: module.clone(),
+            log,
+            reported_trap: false,
+        })
+    }
+
+    pub fn save_state(&mut self) -> SoundState {
+        SoundState {
+            instance: InstanceState::save(
+                &mut self.store,
+                &self.memory,
+                &self.platform_instance,
+                &self.instance,
+            ),
+            sample_index: 0,
+            current_time: 0,
+        }
+    }
+
+    pub fn load_state(&mut self, state: &SoundState) -> Result<()> {
+        state.instance.load(
+            &mut self.store,
+            &self.memory,
+            &self.platform_instance,
+            &self.instance,
+        )
+    }
+
+    /// Resets the deadline before generating the next block of samples.
+    pub fn reset_deadline(&mut self) {
+        self.store.set_epoch_deadline(epoch_deadline(self.timeout));
+    }
+
+    pub fn set_registers(&mut self, data: &[u8]) {
+        self.memory.write(&mut self.store, 80, data).unwrap();
+    }
+
+    pub fn set_time(&mut self, time: i32) {
+        let mem = self.memory.data_mut(&mut self.store);
+        mem[64..68].copy_from_slice(&time.to_le_bytes());
+    }
+
+    /// Calls `snd` for the given sample index, returning a sample clamped to -1..1.
+    /// Even indices are the left channel, odd ones the right channel.
+    pub fn sample(&mut self, index: i32) -> f32 {
+        let s = match self.snd.call(&mut self.store, (index,)) {
+            Ok(s) => s,
+            Err(err) => {
+                // only report the first trap, the sound would keep trapping every sample
+                if !self.reported_trap {
+                    self.reported_trap = true;
+                    eprintln!(
+                        "{}",
+                        TrapReport::new(
+                            &err,
+                            None,
+                            "snd",
+                            self.timeout,
+                            &self.platform_module,
+                            &self.module,
+                            &self.log.lock().unwrap(),
+                        )
+                    );
+                }
+                0.0
+            }
+        };
+        if s.is_nan() {
+            0.0
+        } else {
+            s.max(-1.0).min(1.0)
+        }
+    }
 }
 
 fn init_sound(
     engine: &wasmtime::Engine,
     platform_module: &wasmtime::Module,
     module: &wasmtime::Module,
+    timeout: Duration,
+    sound_stats: Option<Arc<Mutex<TimingStats>>>,
 ) -> Result<Uw8Sound> {
-    let mut store = wasmtime::Store::new(engine, ());
-    store.set_epoch_deadline(60);
-
-    let memory = wasmtime::Memory::new(&mut store, MemoryType::new(4, Some(4)))?;
-
-    let mut linker = wasmtime::Linker::new(engine);
-    linker.define(&store, "env", "memory", memory)?;
-    add_native_functions(&mut linker, &mut store)?;
-
-    let platform_instance = instantiate_platform(&mut linker, &mut store, platform_module)?;
-    let instance = linker.instantiate(&mut store, module)?;
-
-    let snd = instance
-        .get_typed_func::<(i32,), f32>(&mut store, "snd")
-        .or_else(|_| platform_instance.get_typed_func::<(i32,), f32>(&mut store, "sndGes"))?;
+    let playback = Arc::new(Mutex::new(SoundPlayback {
+        sound: SoundInstance::new(engine, platform_module, module, timeout)?,
+        sample_index: 0,
+        current_time: 0,
+    }));
 
     let host = cpal::default_host();
     let device = host
@@ -383,7 +1321,7 @@ fn init_sound(
 
     let sample_rate = config.sample_rate.0 as usize;
 
-    let (tx, rx) = mpsc::sync_channel::<RegisterUpdate>(30);
+    let (tx, rx) = mpsc::sync_channel::<SoundMessage>(30);
 
     struct Resampler {
         resampler: rubato::FftFixedIn<f32>,
@@ -405,29 +1343,46 @@ fn init_sound(
         })
     };
 
-    let mut sample_index = 0;
     let mut pending_updates: Vec<RegisterUpdate> = Vec::with_capacity(30);
-    let mut current_time = 0;
 
+    let callback_playback = playback.clone();
     let mut callback = move |mut outer_buffer: &mut [f32]| {
+        let mut playback = callback_playback.lock().unwrap();
+        let mut sample_index = playback.sample_index;
+        let mut current_time = playback.current_time;
+        let sound = &mut playback.sound;
+
         let mut first_update = true;
-        while let Ok(update) = rx.try_recv() {
-            if first_update {
-                current_time += update.time.wrapping_sub(current_time) / 8;
-                first_update = false;
+        while let Ok(message) = rx.try_recv() {
+            match message {
+                SoundMessage::Update(update) => {
+                    if first_update {
+                        current_time += update.time.wrapping_sub(current_time) / 8;
+                        first_update = false;
+                    }
+                    pending_updates.push(update);
+                }
+                SoundMessage::LoadState(state) => {
+                    if let Err(err) = sound.load_state(&state) {
+                        eprintln!("Failed to restore sound state: {}", err);
+                    }
+                    sample_index = state.sample_index;
+                    current_time = state.current_time;
+                    pending_updates.clear();
+                }
             }
-            pending_updates.push(update);
         }
 
+        let start = Instant::now();
         while !outer_buffer.is_empty() {
-            store.set_epoch_deadline(30);
+            sound.reset_deadline();
             while pending_updates
                 .first()
                 .into_iter()
                 .any(|u| u.time.wrapping_sub(current_time) <= 0)
             {
                 let update = pending_updates.remove(0);
-                memory.write(&mut store, 80, &update.data).unwrap();
+                sound.set_registers(&update.data);
             }
 
             let duration = if let Some(update) = pending_updates.first() {
@@ -439,18 +1394,7 @@ fn init_sound(
 
             let mut buffer = &mut outer_buffer[..step_size];
 
-            {
-                let mem = memory.data_mut(&mut store);
-                mem[64..68].copy_from_slice(&current_time.to_le_bytes());
-            }
-
-            fn clamp_sample(s: f32) -> f32 {
-                if s.is_nan() {
-                    0.0
-                } else {
-                    s.max(-1.0).min(1.0)
-                }
-            }
+            sound.set_time(current_time);
 
             if let Some(ref mut resampler) = resampler {
                 while !buffer.is_empty() {
@@ -462,12 +1406,8 @@ fn init_sound(
                         resampler.input_buffers[0].clear();
                         resampler.input_buffers[1].clear();
                         for _ in 0..resampler.resampler.input_frames_next() {
-                            resampler.input_buffers[0].push(clamp_sample(
-                                snd.call(&mut store, (sample_index,)).unwrap_or(0.0),
-                            ));
-                            resampler.input_buffers[1].push(clamp_sample(
-                                snd.call(&mut store, (sample_index + 1,)).unwrap_or(0.0),
-                            ));
+                            resampler.input_buffers[0].push(sound.sample(sample_index));
+                            resampler.input_buffers[1].push(sound.sample(sample_index + 1));
                             sample_index = sample_index.wrapping_add(2);
                         }
 
@@ -492,7 +1432,7 @@ fn init_sound(
                 }
             } else {
                 for v in buffer {
-                    *v = clamp_sample(snd.call(&mut store, (sample_index,)).unwrap_or(0.0));
+                    *v = sound.sample(sample_index);
                     sample_index = sample_index.wrapping_add(1);
                 }
             }
@@ -500,6 +1440,13 @@ fn init_sound(
             outer_buffer = &mut outer_buffer[step_size..];
             current_time = current_time.wrapping_add((step_size * 500 / sample_rate).max(1) as i32);
         }
+        playback.sample_index = sample_index;
+        playback.current_time = current_time;
+        if let Some(ref sound_stats) = sound_stats {
+            if let Ok(mut sound_stats) = sound_stats.lock() {
+                sound_stats.add(start.elapsed());
+            }
+        }
     };
 
     fn f32_to_i16<F>(mut buffer: &mut [i16], callback: &mut F)
@@ -623,5 +1570,65 @@ fn init_sound(
         }
     };
 
-    Ok(Uw8Sound { stream, tx })
+    Ok(Uw8Sound {
+        stream,
+        tx,
+        playback,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_text_advances_write_position() {
+        let mut mem = vec![0; KEYBOARD_BUFFER + 256];
+        push_text(&mut mem, b"ab");
+        push_text(&mut mem, b"c");
+        assert_eq!(mem[KEYBOARD_POSITIONS], 0);
+        assert_eq!(mem[KEYBOARD_POSITIONS + 1], 3);
+        assert_eq!(&mem[KEYBOARD_BUFFER..KEYBOARD_BUFFER + 3], b"abc");
+    }
+
+    #[test]
+    fn push_text_wraps_around() {
+        let mut mem = vec![0; KEYBOARD_BUFFER + 256];
+        mem[KEYBOARD_POSITIONS] = 250;
+        mem[KEYBOARD_POSITIONS + 1] = 254;
+        push_text(&mut mem, b"xyz");
+        assert_eq!(mem[KEYBOARD_POSITIONS + 1], 1);
+        assert_eq!(mem[KEYBOARD_BUFFER + 254], b'x');
+        assert_eq!(mem[KEYBOARD_BUFFER + 255], b'y');
+        assert_eq!(mem[KEYBOARD_BUFFER], b'z');
+    }
+
+    #[test]
+    fn push_text_drops_characters_when_full() {
+        let mut mem = vec![0; KEYBOARD_BUFFER + 256];
+        mem[KEYBOARD_POSITIONS] = 3;
+        mem[KEYBOARD_POSITIONS + 1] = 1;
+        push_text(&mut mem, b"abc");
+        assert_eq!(mem[KEYBOARD_POSITIONS + 1], 2);
+        assert_eq!(mem[KEYBOARD_BUFFER + 1], b'a');
+        assert_eq!(mem[KEYBOARD_BUFFER + 2], 0);
+    }
+
+    #[test]
+    fn to_uw8_char_maps_control_characters() {
+        assert_eq!(to_uw8_char('\r'), Some(10));
+        assert_eq!(to_uw8_char('\n'), Some(10));
+        assert_eq!(to_uw8_char('\u{8}'), Some(8));
+        assert_eq!(to_uw8_char('\u{7f}'), Some(8));
+        assert_eq!(to_uw8_char('\t'), None);
+    }
+
+    #[test]
+    fn to_uw8_char_keeps_latin1() {
+        assert_eq!(to_uw8_char('A'), Some(b'A'));
+        assert_eq!(to_uw8_char('~'), Some(b'~'));
+        assert_eq!(to_uw8_char('ä'), Some(0xe4));
+        assert_eq!(to_uw8_char('\u{9f}'), None);
+        assert_eq!(to_uw8_char('€'), None);
+    }
 }
@@ -8,16 +8,22 @@ use tokio::sync::broadcast;
 use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
 use warp::{http::Response, Filter};
 
+#[derive(Clone)]
+enum Event {
+    Load,
+    Error(String),
+}
+
 pub struct RunWebServer {
     cart: Arc<Mutex<Vec<u8>>>,
-    tx: broadcast::Sender<()>,
+    tx: broadcast::Sender<Event>,
     socket_addr: SocketAddr,
 }
 
 impl RunWebServer {
     pub fn new() -> RunWebServer {
         let cart = Arc::new(Mutex::new(Vec::new()));
-        let (tx, _) = broadcast::channel(1);
+        let (tx, _) = broadcast::channel(4);
 
         let socket_addr = "127.0.0.1:3030"
             .parse::<SocketAddr>()
@@ -44,11 +50,17 @@ impl RunWebServer {
 
                 let events = warp::path("events").and(warp::get()).map(move || {
                     fn event_stream(
-                        tx: &broadcast::Sender<()>,
+                        tx: &broadcast::Sender<Event>,
                     ) -> impl Stream<Item = Result<warp::sse::Event, std::convert::Infallible>>
                     {
-                        BroadcastStream::new(tx.subscribe())
-                            .map(|_| Ok(warp::sse::Event::default().data("L")))
+                        // a lagging client just reloads the current cart
+                        BroadcastStream::new(tx.subscribe()).map(|event| {
+                            let data = match event {
+                                Ok(Event::Error(message)) => format!("E{}", message),
+                                _ => "L".to_string(),
+                            };
+                            Ok(warp::sse::Event::default().data(data))
+                        })
                     }
                     warp::sse::reply(warp::sse::keep_alive().stream(event_stream(&server_tx)))
                 });
@@ -76,10 +88,14 @@ impl super::Runtime for RunWebServer {
             lock.clear();
             lock.extend_from_slice(module_data);
         }
-        let _ignore_result = self.tx.send(());
+        let _ignore_result = self.tx.send(Event::Load);
         Ok(())
     }
 
+    fn show_error(&mut self, message: &str) {
+        let _ignore_result = self.tx.send(Event::Error(message.to_string()));
+    }
+
     fn is_open(&self) -> bool {
         true
     }
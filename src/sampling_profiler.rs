@@ -0,0 +1,93 @@
+use anyhow::{bail, Result};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use wasmtime::{AsContext, Engine, GuestProfiler, Module};
+
+/// Samples the wasm call stack of a cart every time the epoch is incremented.
+///
+/// Function names are resolved from the name section of the modules, so carts
+/// should be compiled with debug info for useful profiles.
+pub(crate) struct Sampler {
+    profiler: GuestProfiler,
+    last_sample: Instant,
+    call_start: Instant,
+    timeout: Duration,
+}
+
+impl Sampler {
+    pub fn new(interval: Duration, platform_module: &Module, module: &Module) -> Sampler {
+        let profiler = GuestProfiler::new(
+            "cart",
+            interval,
+            [
+                ("platform".to_string(), platform_module.clone()),
+                ("cart".to_string(), module.clone()),
+            ],
+        );
+        let now = Instant::now();
+        Sampler {
+            profiler,
+            last_sample: now,
+            call_start: now,
+            timeout: Duration::ZERO,
+        }
+    }
+
+    /// Marks the start of a call into the cart, which may run for `timeout`.
+    pub fn begin_call(&mut self, timeout: Duration) {
+        let now = Instant::now();
+        self.timeout = timeout;
+        self.call_start = now;
+        self.last_sample = now;
+    }
+
+    /// Takes a sample, failing if the current call has exceeded the timeout.
+    pub fn sample(&mut self, store: impl AsContext) -> Result<()> {
+        let now = Instant::now();
+        if now - self.call_start > self.timeout {
+            bail!("Cart timed out while profiling");
+        }
+        self.profiler.sample(store, now - self.last_sample);
+        self.last_sample = now;
+        Ok(())
+    }
+
+    /// Writes the collected samples in the Firefox profiler json format.
+    pub fn finish<W: Write>(self, out: W) -> Result<()> {
+        self.profiler.finish(out)
+    }
+}
+
+/// Increments the epoch of an engine at a fixed interval until dropped, to drive
+/// the sampler at a higher rate than the watchdog.
+pub(crate) struct EpochTicker {
+    stop: Arc<AtomicBool>,
+}
+
+impl EpochTicker {
+    pub fn new(engine: &Engine, interval: Duration) -> EpochTicker {
+        let stop = Arc::new(AtomicBool::new(false));
+        {
+            let engine = engine.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(interval);
+                    engine.increment_epoch();
+                }
+            });
+        }
+        EpochTicker { stop }
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+pub(crate) type SharedSampler = Arc<Mutex<Option<Sampler>>>;
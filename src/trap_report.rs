@@ -0,0 +1,138 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+use wasmtime::{Module, Trap, WasmBacktrace};
+
+/// Number of log lines kept for trap reports.
+const LOG_LINES: usize = 8;
+
+/// Collects the output of `logChar`, printing complete lines and remembering the
+/// last few of them.
+#[derive(Default)]
+pub(crate) struct LogOutput {
+    line: String,
+    recent: VecDeque<String>,
+}
+
+impl LogOutput {
+    pub fn push_char(&mut self, c: u8) {
+        if c == 10 {
+            println!("{}", self.line);
+            if self.recent.len() == LOG_LINES {
+                self.recent.pop_front();
+            }
+            self.recent.push_back(std::mem::take(&mut self.line));
+        } else {
+            self.line.push(c as char);
+        }
+    }
+
+    /// The last complete lines followed by the current unterminated one.
+    fn recent(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.recent.iter().cloned().collect();
+        if !self.line.is_empty() {
+            lines.push(self.line.clone());
+        }
+        lines
+    }
+}
+
+/// A readable report of a cart that trapped, hung or otherwise failed in one of its
+/// exports.
+#[derive(Debug)]
+pub struct TrapReport {
+    /// The export that was running, eg. `upd`.
+    pub export: &'static str,
+    /// The frame in which the cart trapped, `None` outside of a frame.
+    pub frame: Option<u32>,
+    pub kind: String,
+    pub backtrace: Vec<String>,
+    pub log: Vec<String>,
+}
+
+impl TrapReport {
+    pub(crate) fn new(
+        error: &anyhow::Error,
+        frame: Option<u32>,
+        export: &'static str,
+        timeout: Duration,
+        platform_module: &Module,
+        module: &Module,
+        log: &LogOutput,
+    ) -> TrapReport {
+        let backtrace = error
+            .downcast_ref::<WasmBacktrace>()
+            .map(|backtrace| {
+                backtrace
+                    .frames()
+                    .iter()
+                    .map(|frame| {
+                        let module_name = if Module::same(frame.module(), module) {
+                            "cart"
+                        } else if Module::same(frame.module(), platform_module) {
+                            "platform"
+                        } else {
+                            "?"
+                        };
+                        let function = frame
+                            .func_name()
+                            .map(|name| name.to_string())
+                            .unwrap_or_else(|| format!("func[{}]", frame.func_index()));
+                        match frame.module_offset() {
+                            Some(offset) => format!("{}!{} @ {:#x}", module_name, function, offset),
+                            None => format!("{}!{}", module_name, function),
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let kind = match error.downcast_ref::<Trap>() {
+            Some(Trap::Interrupt) => {
+                let mut kind = format!("hang, did not return within {}ms", timeout.as_millis());
+                if let Some(top) = backtrace.first() {
+                    kind += &format!(", stuck in {}", top);
+                }
+                kind
+            }
+            Some(trap) => trap.to_string(),
+            None => error.root_cause().to_string(),
+        };
+
+        TrapReport {
+            export,
+            frame,
+            kind,
+            backtrace,
+            log: log.recent(),
+        }
+    }
+}
+
+impl fmt::Display for TrapReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.frame {
+            Some(frame) => write!(
+                f,
+                "Trap in {} in frame {}: {}",
+                self.export, frame, self.kind
+            )?,
+            None => write!(f, "Trap in {}: {}", self.export, self.kind)?,
+        }
+        if !self.backtrace.is_empty() {
+            write!(f, "\nBacktrace:")?;
+            for (index, frame) in self.backtrace.iter().enumerate() {
+                write!(f, "\n  {}: {}", index, frame)?;
+            }
+        }
+        if !self.log.is_empty() {
+            write!(f, "\nLast log output:")?;
+            for line in &self.log {
+                write!(f, "\n  {}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TrapReport {}
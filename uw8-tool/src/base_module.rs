@@ -184,6 +184,40 @@ impl BaseModule {
             None,
         );
 
+        add_function(
+            &mut functions,
+            &type_map,
+            "isPlayerButtonPressed",
+            &[I32, I32],
+            Some(I32),
+        );
+        add_function(
+            &mut functions,
+            &type_map,
+            "isPlayerButtonTriggered",
+            &[I32, I32],
+            Some(I32),
+        );
+
+        add_function(&mut functions, &type_map, "mouseX", &[], Some(I32));
+        add_function(&mut functions, &type_map, "mouseY", &[], Some(I32));
+        add_function(
+            &mut functions,
+            &type_map,
+            "isMouseButtonPressed",
+            &[I32],
+            Some(I32),
+        );
+        add_function(
+            &mut functions,
+            &type_map,
+            "isMouseButtonTriggered",
+            &[I32],
+            Some(I32),
+        );
+
+        add_function(&mut functions, &type_map, "readChar", &[], Some(I32));
+
         for i in functions.len()..64 {
             add_function(
                 &mut functions,
@@ -410,12 +444,22 @@ fn type_to_str(ty: ValType) -> &'static str {
     }
 }
 
+/// The named addresses of the memory map, in ascending order.
+pub fn memory_map() -> impl Iterator<Item = (&'static str, u32)> {
+    CONSTANTS
+        .iter()
+        .copied()
+        .filter(|(name, _)| !name.starts_with("BUTTON_") && !name.starts_with("MOUSE_BUTTON_"))
+}
+
 const CONSTANTS: &[(&str, u32)] = &[
     ("TIME_MS", 0x40),
     ("GAMEPAD", 0x44),
+    ("MOUSE", 0x70),
     ("FRAMEBUFFER", 0x78),
     ("PALETTE", 0x13000),
     ("FONT", 0x13400),
+    ("KEYBOARD", 0x13c00),
     ("USER_MEM", 0x14000),
     ("BUTTON_UP", 0),
     ("BUTTON_DOWN", 1),
@@ -425,4 +469,7 @@ const CONSTANTS: &[(&str, u32)] = &[
     ("BUTTON_B", 5),
     ("BUTTON_X", 6),
     ("BUTTON_Y", 7),
+    ("MOUSE_BUTTON_LEFT", 0),
+    ("MOUSE_BUTTON_RIGHT", 1),
+    ("MOUSE_BUTTON_MIDDLE", 2),
 ];
@@ -0,0 +1,24 @@
+use anyhow::Result;
+
+const GLOBAL_EXPORT_PREFIX: &str = "__uw8_global";
+
+/// Adds an export for every mutable global defined in the module, so that a runtime
+/// can read and restore them, eg. for save states.
+pub fn export_globals(module_data: &[u8]) -> Result<Vec<u8>> {
+    let mut module = walrus::Module::from_buffer(module_data)?;
+
+    let globals: Vec<_> = module
+        .globals
+        .iter()
+        .filter(|global| global.mutable && matches!(global.kind, walrus::GlobalKind::Local(_)))
+        .map(|global| global.id())
+        .collect();
+
+    for (index, id) in globals.into_iter().enumerate() {
+        module
+            .exports
+            .add(&format!("{}{}", GLOBAL_EXPORT_PREFIX, index), id);
+    }
+
+    Ok(module.emit_wasm())
+}
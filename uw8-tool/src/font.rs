@@ -0,0 +1,28 @@
+use anyhow::{bail, Result};
+
+/// Converts the 128x128 font image (16x16 characters of 8x8 pixels) to the MicroW8 font
+/// format: 8 bytes per character, one byte per row with the leftmost pixel in the msb.
+pub fn convert_font(png: &[u8]) -> Result<Vec<u8>> {
+    let image = lodepng::decode32(png)?;
+
+    if image.width != 128 || image.height != 128 {
+        bail!("Font image has to be 128x128 pixels");
+    }
+
+    let mut font = vec![];
+    for char in 0..256 {
+        for y in 0..8 {
+            let mut byte = 0u8;
+            let base = (char % 16 * 8) + (char / 16 * 8 + y) * 128;
+            for x in 0..8 {
+                byte += byte;
+                if image.buffer[base + x].r > 128 {
+                    byte |= 1;
+                }
+            }
+            font.push(byte);
+        }
+    }
+
+    Ok(font)
+}
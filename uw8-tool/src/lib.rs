@@ -1,9 +1,15 @@
 mod base_module;
+mod export_globals;
 mod filter_exports;
+mod font;
+mod name_functions;
 mod pack;
 
-pub use base_module::BaseModule;
+pub use base_module::{memory_map, BaseModule};
+pub use export_globals::export_globals;
 pub use filter_exports::filter_exports;
+pub use font::convert_font;
+pub use name_functions::name_exported_functions;
 pub use pack::{pack, pack_file, unpack, unpack_file, PackConfig};
 
 pub fn compressed_size(cart: &[u8]) -> f32 {
@@ -0,0 +1,18 @@
+use anyhow::Result;
+
+/// Names all unnamed exported functions after their export, so that backtraces
+/// show eg. the api functions of the platform module by name.
+pub fn name_exported_functions(module_data: &[u8]) -> Result<Vec<u8>> {
+    let mut module = walrus::Module::from_buffer(module_data)?;
+
+    for export in module.exports.iter() {
+        if let walrus::ExportItem::Function(id) = export.item {
+            let function = module.funcs.get_mut(id);
+            if function.name.is_none() {
+                function.name = Some(export.name.clone());
+            }
+        }
+    }
+
+    Ok(module.emit_wasm())
+}
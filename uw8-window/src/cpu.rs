@@ -1,8 +1,13 @@
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::time::Instant;
 
-use crate::{Input, WindowImpl};
+use crate::{
+    filter_name, gpu::scale_mode::ScaleMode, software_filter::SoftwareFilter, Input, KeyBindings,
+    WindowConfig, WindowImpl,
+};
 use anyhow::Result;
-use minifb::{Key, WindowOptions};
+use minifb::{InputCallback, Key, MouseButton, MouseMode, WindowOptions};
 
 static GAMEPAD_KEYS: &[Key] = &[
     Key::Up,
@@ -18,26 +23,56 @@ static GAMEPAD_KEYS: &[Key] = &[
 pub struct Window {
     window: minifb::Window,
     buffer: Vec<u32>,
+    filter: SoftwareFilter,
+    scale_mode: ScaleMode,
+    key_bindings: KeyBindings,
+    text: Rc<RefCell<String>>,
+    text_input: bool,
+}
+
+/// Collects the typed characters reported by minifb.
+struct TextInput(Rc<RefCell<String>>);
+
+impl InputCallback for TextInput {
+    fn add_char(&mut self, uni_char: u32) {
+        if let Some(c) = char::from_u32(uni_char) {
+            self.0.borrow_mut().push(c);
+        }
+    }
 }
 
 impl Window {
-    pub fn new() -> Result<Window> {
+    pub fn new(config: WindowConfig) -> Result<Window> {
         #[cfg(target_os = "windows")]
         unsafe {
             winapi::um::timeapi::timeBeginPeriod(1);
         }
 
-        let buffer: Vec<u32> = vec![0; 320 * 240];
-
         let options = WindowOptions {
-            scale: minifb::Scale::X2,
-            scale_mode: minifb::ScaleMode::AspectRatioStretch,
+            // the screen is scaled in software to match the scale modes of the gpu window
+            scale_mode: minifb::ScaleMode::UpperLeft,
             resize: true,
             ..Default::default()
         };
-        let window = minifb::Window::new("MicroW8", 320, 240, options).unwrap();
+        let mut window = minifb::Window::new(
+            "MicroW8",
+            (320. * config.scale).round() as usize,
+            (240. * config.scale).round() as usize,
+            options,
+        )
+        .unwrap();
+        let text = Rc::new(RefCell::new(String::new()));
+        window.set_input_callback(Box::new(TextInput(text.clone())));
 
-        Ok(Window { window, buffer })
+        Ok(Window {
+            window,
+            buffer: Vec::new(),
+            filter: SoftwareFilter::new(config.filter),
+            scale_mode: config.scale_mode,
+            key_bindings: config.key_bindings,
+            text,
+            text_input: false,
+        })
     }
 }
 
@@ -45,7 +80,11 @@ impl WindowImpl for Window {
     fn begin_frame(&mut self) -> Input {
         let mut gamepads = [0u8; 4];
         for key in self.window.get_keys() {
-            if let Some(index) = GAMEPAD_KEYS
+            if let Some(buttons) = self.key_bindings.buttons(&format!("{:?}", key)) {
+                for (gamepad, mask) in buttons {
+                    gamepads[gamepad] |= mask;
+                }
+            } else if let Some(index) = GAMEPAD_KEYS
                 .iter()
                 .enumerate()
                 .find(|(_, &k)| k == key)
@@ -55,28 +94,110 @@ impl WindowImpl for Window {
             }
         }
 
+        let (width, height) = self.window.get_size();
+        let (x, y) = self
+            .window
+            .get_unscaled_mouse_pos(MouseMode::Pass)
+            .unwrap_or_default();
+        let mut mouse_buttons = 0;
+        for (bit, button) in [MouseButton::Left, MouseButton::Right, MouseButton::Middle]
+            .into_iter()
+            .enumerate()
+        {
+            if self.window.get_mouse_down(button) {
+                mouse_buttons |= 1 << bit;
+            }
+        }
+
+        // hotkeys on character keys need Ctrl while the cart reads text
+        let ctrl =
+            self.window.is_key_down(Key::LeftCtrl) || self.window.is_key_down(Key::RightCtrl);
+        let hotkeys = !self.text_input || ctrl;
+        let mut text = std::mem::take(&mut *self.text.borrow_mut());
+        if ctrl {
+            // Ctrl is used for the hotkeys, not for typing
+            text.clear();
+        }
+
+        let mut notification = None;
+        if hotkeys && self.window.is_key_pressed(Key::M, minifb::KeyRepeat::No) {
+            self.scale_mode = self.scale_mode.next();
+            notification = Some(format!("scale: {}", self.scale_mode.name()));
+        }
+        for (filter, key) in [Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5]
+            .into_iter()
+            .enumerate()
+        {
+            if hotkeys && self.window.is_key_pressed(key, minifb::KeyRepeat::No) {
+                self.filter.filter = filter as u32 + 1;
+                notification = Some(format!("filter: {}", filter_name(self.filter.filter)));
+            }
+        }
+
         Input {
             gamepads,
-            reset: self.window.is_key_pressed(Key::R, minifb::KeyRepeat::No),
+            mouse_position: self
+                .scale_mode
+                .window_to_screen(width as f32, height as f32, x, y),
+            mouse_buttons,
+            text,
+            reset: hotkeys && self.window.is_key_pressed(Key::R, minifb::KeyRepeat::No),
+            save_state: self.window.is_key_pressed(Key::F5, minifb::KeyRepeat::No),
+            load_state: self.window.is_key_pressed(Key::F6, minifb::KeyRepeat::No),
+            pause: hotkeys && self.window.is_key_pressed(Key::P, minifb::KeyRepeat::No),
+            step: hotkeys && self.window.is_key_pressed(Key::N, minifb::KeyRepeat::Yes),
+            slower: hotkeys
+                && (self
+                    .window
+                    .is_key_pressed(Key::Minus, minifb::KeyRepeat::No)
+                    || self
+                        .window
+                        .is_key_pressed(Key::NumPadMinus, minifb::KeyRepeat::No)),
+            faster: hotkeys
+                && (self
+                    .window
+                    .is_key_pressed(Key::Equal, minifb::KeyRepeat::No)
+                    || self
+                        .window
+                        .is_key_pressed(Key::NumPadPlus, minifb::KeyRepeat::No)),
+            toggle_inspector: self.window.is_key_pressed(Key::F9, minifb::KeyRepeat::No),
+            next_inspector_range: self.window.is_key_pressed(Key::F10, minifb::KeyRepeat::No),
+            inspector_scroll: self
+                .window
+                .is_key_pressed(Key::PageDown, minifb::KeyRepeat::Yes)
+                as i32
+                - self
+                    .window
+                    .is_key_pressed(Key::PageUp, minifb::KeyRepeat::Yes) as i32,
+            screenshot: self.window.is_key_pressed(Key::F7, minifb::KeyRepeat::No),
+            toggle_gif_recording: self.window.is_key_pressed(Key::F8, minifb::KeyRepeat::No),
+            toggle_debug_overlay: self.window.is_key_pressed(Key::F11, minifb::KeyRepeat::No),
+            notification,
         }
     }
 
     fn end_frame(&mut self, framebuffer: &[u8], palette: &[u8], next_frame: Instant) {
-        for (i, &color_index) in framebuffer.iter().enumerate() {
-            let offset = color_index as usize * 4;
-            self.buffer[i] = 0xff000000
-                | ((palette[offset] as u32) << 16)
-                | ((palette[offset + 1] as u32) << 8)
-                | palette[offset + 2] as u32;
-        }
+        let (width, height) = self.window.get_size();
+        let (width, height) = (width.max(1), height.max(1));
+        self.filter.set_screen(framebuffer, palette);
+        self.filter
+            .render(&mut self.buffer, width, height, self.scale_mode);
         self.window
-            .update_with_buffer(&self.buffer, 320, 240)
+            .update_with_buffer(&self.buffer, width, height)
             .unwrap();
         if let Some(sleep) = next_frame.checked_duration_since(Instant::now()) {
             std::thread::sleep(sleep);
         }
     }
 
+    fn set_title(&mut self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    fn set_text_input(&mut self, text_input: bool) {
+        self.text_input = text_input;
+    }
+
     fn is_open(&self) -> bool {
         self.window.is_open() && !self.window.is_key_down(Key::Escape)
     }
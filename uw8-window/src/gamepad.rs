@@ -0,0 +1,104 @@
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+
+/// The buttons in the order of the bits of a MicroW8 gamepad byte, using the same
+/// standard mapping as the web runtime.
+const BUTTONS: [Button; 8] = [
+    Button::DPadUp,
+    Button::DPadDown,
+    Button::DPadLeft,
+    Button::DPadRight,
+    Button::South,
+    Button::East,
+    Button::West,
+    Button::North,
+];
+
+/// How far the left stick has to be pushed to count as a D-pad press.
+const AXIS_THRESHOLD: f32 = 0.5;
+
+/// Physical gamepads, assigned to the four MicroW8 gamepads in the order they are
+/// connected. A disconnected gamepad frees its slot for the next one plugged in.
+pub struct Gamepads {
+    gilrs: Option<Gilrs>,
+    slots: [Option<GamepadId>; 4],
+}
+
+impl Gamepads {
+    pub fn new() -> Gamepads {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(err) => {
+                eprintln!("Failed to initialize gamepad support: {}", err);
+                None
+            }
+        };
+        let mut slots = [None; 4];
+        if let Some(ref gilrs) = gilrs {
+            for (id, _) in gilrs.gamepads() {
+                connect(&mut slots, id);
+            }
+        }
+        Gamepads { gilrs, slots }
+    }
+
+    /// Handles connects and disconnects and returns the state of the four gamepads
+    /// and whether start was pressed on any of them.
+    pub fn poll(&mut self) -> ([u8; 4], bool) {
+        let mut state = [0u8; 4];
+        let mut reset = false;
+        let Some(ref mut gilrs) = self.gilrs else {
+            return (state, reset);
+        };
+
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                EventType::Connected => connect(&mut self.slots, event.id),
+                EventType::Disconnected => {
+                    for slot in &mut self.slots {
+                        if *slot == Some(event.id) {
+                            *slot = None;
+                        }
+                    }
+                }
+                EventType::ButtonPressed(Button::Start, _) => reset = true,
+                _ => (),
+            }
+        }
+
+        for (slot, state) in self.slots.iter().zip(state.iter_mut()) {
+            let Some(gamepad) = slot.and_then(|id| gilrs.connected_gamepad(id)) else {
+                continue;
+            };
+            for (bit, &button) in BUTTONS.iter().enumerate() {
+                if gamepad.is_pressed(button) {
+                    *state |= 1 << bit;
+                }
+            }
+            let x = gamepad.value(Axis::LeftStickX);
+            let y = gamepad.value(Axis::LeftStickY);
+            if y > AXIS_THRESHOLD {
+                *state |= 1;
+            }
+            if y < -AXIS_THRESHOLD {
+                *state |= 2;
+            }
+            if x < -AXIS_THRESHOLD {
+                *state |= 4;
+            }
+            if x > AXIS_THRESHOLD {
+                *state |= 8;
+            }
+        }
+
+        (state, reset)
+    }
+}
+
+fn connect(slots: &mut [Option<GamepadId>; 4], id: GamepadId) {
+    if slots.contains(&Some(id)) {
+        return;
+    }
+    if let Some(slot) = slots.iter_mut().find(|slot| slot.is_none()) {
+        *slot = Some(id);
+    }
+}
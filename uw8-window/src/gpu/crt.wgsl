@@ -5,6 +5,7 @@ struct VertexOutput {
 
 struct Uniforms {
     texture_scale: vec4<f32>,
+    screen_offset: vec4<f32>,
 }
 
 @group(0) @binding(1) var<uniform> uniforms: Uniforms;
@@ -17,7 +18,7 @@ fn vs_main(
     let i = in_vertex_index / 3u + in_vertex_index % 3u;
     let x = -1.0 + f32(i % 2u) * 322.0;
     let y = -1.0 + f32(i / 2u) * 242.0;
-    out.clip_position = vec4<f32>((vec2<f32>(x, y) - vec2<f32>(160.0, 120.0)) * uniforms.texture_scale.xy, 0.0, 1.0);
+    out.clip_position = vec4<f32>((vec2<f32>(x, y) - vec2<f32>(160.0, 120.0)) * uniforms.texture_scale.xy + uniforms.screen_offset.xy, 0.0, 1.0);
     out.tex_coords = vec2<f32>(x, y);
     return out;
 }
@@ -41,7 +42,7 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let pixel = vec2<i32>(pixelf);
     
     let offset_x = o.xxxx + vec4<f32>(-0.125, 0.375, 0.125, -0.375) * uniforms.texture_scale.z;
-    let offset_y = o.yyyy + vec4<f32>(-0.375, -0.125, 0.375, 0.125) * uniforms.texture_scale.z;
+    let offset_y = o.yyyy + vec4<f32>(-0.375, -0.125, 0.375, 0.125) * uniforms.texture_scale.w;
     
     var offset_x0 = max(abs(offset_x + vec4<f32>(-1.0)) - vec4<f32>(0.5), vec4<f32>(0.0));
     var offset_x1 = max(abs(offset_x) - vec4<f32>(0.5), vec4<f32>(0.0));
@@ -0,0 +1,239 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{bail, Result};
+use wgpu::util::DeviceExt;
+use winit::dpi::PhysicalSize;
+
+use super::{
+    scale_mode::{self, ScaleMode},
+    Filter,
+};
+
+/// How often the shader file is checked for changes.
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A filter using a fragment shader loaded from disk, which is reloaded whenever the
+/// file changes. The interface of the shader is described in `custom_prelude.wgsl`.
+pub struct CustomFilter {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+    start: Instant,
+    uniforms: Uniforms,
+    uniform_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    surface_format: wgpu::TextureFormat,
+    pipeline: Option<wgpu::RenderPipeline>,
+}
+
+impl CustomFilter {
+    pub fn new(
+        device: &wgpu::Device,
+        screen: &wgpu::TextureView,
+        resolution: PhysicalSize<u32>,
+        surface_format: wgpu::TextureFormat,
+        scale_mode: ScaleMode,
+        path: &Path,
+    ) -> CustomFilter {
+        let uniforms = Uniforms {
+            scale: scale_mode.uniforms_from_resolution(resolution),
+            resolution: [resolution.width as f32, resolution.height as f32],
+            time: 0.0,
+            _padding: 0.0,
+        };
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&[uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&screen),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+            label: None,
+        });
+
+        let mut filter = CustomFilter {
+            path: path.to_path_buf(),
+            modified: None,
+            last_check: Instant::now(),
+            start: Instant::now(),
+            uniforms,
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+            surface_format,
+            pipeline: None,
+        };
+        filter.reload(device);
+        filter
+    }
+
+    /// Loads the shader again, keeping the previous one if it doesn't compile.
+    fn reload(&mut self, device: &wgpu::Device) {
+        self.modified = modified_time(&self.path);
+        match self.create_pipeline(device) {
+            Ok(pipeline) => {
+                self.pipeline = Some(pipeline);
+                println!("Loaded shader {}", self.path.display());
+            }
+            Err(err) => eprintln!("Failed to load shader {}: {}", self.path.display(), err),
+        }
+    }
+
+    fn create_pipeline(&self, device: &wgpu::Device) -> Result<wgpu::RenderPipeline> {
+        // the prelude goes after the shader, so the line numbers in compile errors match
+        // the file. WGSL allows using declarations before they appear.
+        let source = format!(
+            "{}\n{}",
+            std::fs::read_to_string(&self.path)?,
+            include_str!("custom_prelude.wgsl")
+        );
+
+        // compile errors are reported to the error scope instead of panicking
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&self.bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: self.surface_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+        });
+
+        if let Some(err) = pollster::block_on(device.pop_error_scope()) {
+            bail!("{}", err);
+        }
+        Ok(render_pipeline)
+    }
+}
+
+impl Filter for CustomFilter {
+    fn resize(&mut self, queue: &wgpu::Queue, new_size: PhysicalSize<u32>, scale_mode: ScaleMode) {
+        self.uniforms.scale = scale_mode.uniforms_from_resolution(new_size);
+        self.uniforms.resolution = [new_size.width as f32, new_size.height as f32];
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniforms]),
+        );
+    }
+
+    fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.last_check.elapsed() >= RELOAD_CHECK_INTERVAL {
+            self.last_check = Instant::now();
+            if modified_time(&self.path) != self.modified {
+                self.reload(device);
+            }
+        }
+        self.uniforms.time = self.start.elapsed().as_secs_f32();
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniforms]),
+        );
+    }
+
+    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if let Some(ref pipeline) = self.pipeline {
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &self.bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Uniforms {
+    scale: scale_mode::Uniforms,
+    resolution: [f32; 2],
+    time: f32,
+    _padding: f32,
+}
@@ -0,0 +1,39 @@
+// Appended to the shaders given with --shader. A custom shader only needs to define
+// the fragment entry point:
+//
+// @fragment
+// fn fs_main(in: VertexOutput) -> @location(0) vec4<f32>
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // position in the 320x240 screen in screen pixels
+    @location(0) tex_coords: vec2<f32>,
+}
+
+struct Uniforms {
+    texture_scale: vec4<f32>,
+    screen_offset: vec4<f32>,
+    // size of the window in window pixels
+    resolution: vec2<f32>,
+    // seconds since the window was opened
+    time: f32,
+}
+
+// the 320x240 screen with the palette already applied
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var linear_sampler: sampler;
+@group(0) @binding(2) var<uniform> uniforms: Uniforms;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let i = in_vertex_index / 3u + in_vertex_index % 3u;
+    let x = 0.0 + f32(i % 2u) * 320.0;
+    let y = 0.0 + f32(i / 2u) * 240.0;
+    out.clip_position = vec4<f32>((vec2<f32>(x, y) - vec2<f32>(160.0, 120.0)) * uniforms.texture_scale.xy + uniforms.screen_offset.xy, 0.0, 1.0);
+    out.tex_coords = vec2<f32>(x, y);
+    return out;
+}
+
@@ -5,6 +5,7 @@ struct VertexOutput {
 
 struct Uniforms {
     texture_scale: vec4<f32>,
+    screen_offset: vec4<f32>,
 }
 
 @group(0) @binding(2) var<uniform> uniforms: Uniforms;
@@ -17,7 +18,7 @@ fn vs_main(
     let i = in_vertex_index / 3u + in_vertex_index % 3u;
     let x = 0.0 + f32(i % 2u) * 320.0;
     let y = 0.0 + f32(i / 2u) * 240.0;
-    out.clip_position = vec4<f32>((vec2<f32>(x, y) - vec2<f32>(160.0, 120.0)) * uniforms.texture_scale.xy, 0.0, 1.0);
+    out.clip_position = vec4<f32>((vec2<f32>(x, y) - vec2<f32>(160.0, 120.0)) * uniforms.texture_scale.xy + uniforms.screen_offset.xy, 0.0, 1.0);
     out.tex_coords = vec2<f32>(x, y);
     return out;
 }
@@ -1,23 +1,26 @@
-use crate::{Input, WindowConfig, WindowImpl};
+use crate::{filter_name, small_font, Input, KeyBindings, WindowConfig, WindowImpl};
 use anyhow::{anyhow, Result};
 use scale_mode::ScaleMode;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 use winit::{
-    dpi::PhysicalSize,
-    event::{Event, VirtualKeyCode, WindowEvent},
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, Event, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::{Fullscreen, WindowBuilder},
+    window::{Fullscreen, Icon, WindowBuilder},
 };
 
 use winit::platform::run_return::EventLoopExtRunReturn;
 
 mod crt;
+mod custom;
 mod fast_crt;
 pub mod scale_mode;
 mod square;
 
 use crt::CrtFilter;
+use custom::CustomFilter;
 use fast_crt::FastCrtFilter;
 use square::SquareFilter;
 
@@ -30,9 +33,16 @@ pub struct Window {
     palette_screen_mode: PaletteScreenMode,
     surface_config: wgpu::SurfaceConfiguration,
     filter: Box<dyn Filter>,
+    shader: Option<PathBuf>,
     event_loop: EventLoop<()>,
     window: winit::window::Window,
     gamepads: [u8; 4],
+    key_bindings: KeyBindings,
+    cursor_position: PhysicalPosition<f64>,
+    mouse_buttons: u8,
+    text: String,
+    modifiers: ModifiersState,
+    text_input: bool,
     next_frame: Instant,
     is_fullscreen: bool,
     is_open: bool,
@@ -50,6 +60,7 @@ impl Window {
                 ))
                 .with_min_inner_size(PhysicalSize::new(320u32, 240))
                 .with_title("MicroW8")
+                .with_window_icon(window_icon())
                 .with_fullscreen(if window_config.fullscreen {
                     Some(Fullscreen::Borderless(None))
                 } else {
@@ -94,6 +105,7 @@ impl Window {
                 surface_config.format,
                 window_config.filter,
                 window_config.scale_mode,
+                window_config.shader.as_deref(),
             );
 
             surface.configure(&device, &surface_config);
@@ -109,7 +121,14 @@ impl Window {
                 palette_screen_mode,
                 surface_config,
                 filter,
+                shader: window_config.shader,
                 gamepads: [0; 4],
+                key_bindings: window_config.key_bindings,
+                cursor_position: PhysicalPosition::default(),
+                mouse_buttons: 0,
+                text: String::new(),
+                modifiers: ModifiersState::empty(),
+                text_input: false,
                 next_frame: Instant::now(),
                 is_fullscreen: window_config.fullscreen,
                 is_open: true,
@@ -124,6 +143,19 @@ impl Window {
 impl WindowImpl for Window {
     fn begin_frame(&mut self) -> Input {
         let mut reset = false;
+        let mut save_state = false;
+        let mut load_state = false;
+        let mut pause = false;
+        let mut step = false;
+        let mut slower = false;
+        let mut faster = false;
+        let mut toggle_inspector = false;
+        let mut next_inspector_range = false;
+        let mut inspector_scroll = 0;
+        let mut screenshot = false;
+        let mut toggle_gif_recording = false;
+        let mut toggle_debug_overlay = false;
+        let mut notification = None;
         self.event_loop.run_return(|event, _, control_flow| {
             *control_flow = ControlFlow::WaitUntil(self.next_frame);
             let mut new_filter = None;
@@ -139,6 +171,29 @@ impl WindowImpl for Window {
                         self.is_open = false;
                         *control_flow = ControlFlow::Exit;
                     }
+                    WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers,
+                    WindowEvent::ReceivedCharacter(c) => {
+                        // Ctrl is used for the hotkeys, not for typing
+                        if !self.modifiers.ctrl() {
+                            self.text.push(c);
+                        }
+                    }
+                    WindowEvent::CursorMoved { position, .. } => {
+                        self.cursor_position = position;
+                    }
+                    WindowEvent::MouseInput { state, button, .. } => {
+                        let mask = match button {
+                            MouseButton::Left => 1,
+                            MouseButton::Right => 2,
+                            MouseButton::Middle => 4,
+                            _ => 0,
+                        };
+                        if state == ElementState::Pressed {
+                            self.mouse_buttons |= mask;
+                        } else {
+                            self.mouse_buttons &= !mask;
+                        }
+                    }
                     WindowEvent::KeyboardInput { input, .. } => {
                         fn gamepad_button(input: &winit::event::KeyboardInput) -> u8 {
                             match input.scancode {
@@ -155,13 +210,15 @@ impl WindowImpl for Window {
                                 },
                             }
                         }
+                        // hotkeys on character keys need Ctrl while the cart reads text
+                        let hotkeys = !self.text_input || self.modifiers.ctrl();
                         if input.state == winit::event::ElementState::Pressed {
                             match input.virtual_keycode {
                                 Some(VirtualKeyCode::Escape) => {
                                     self.is_open = false;
                                     *control_flow = ControlFlow::Exit;
                                 }
-                                Some(VirtualKeyCode::F) => {
+                                Some(VirtualKeyCode::F) if hotkeys => {
                                     let fullscreen = if self.window.fullscreen().is_some() {
                                         None
                                     } else {
@@ -170,11 +227,10 @@ impl WindowImpl for Window {
                                     self.is_fullscreen = fullscreen.is_some();
                                     self.window.set_fullscreen(fullscreen);
                                 }
-                                Some(VirtualKeyCode::M) => {
-                                    self.scale_mode = match self.scale_mode {
-                                        ScaleMode::Fit => ScaleMode::Fill,
-                                        ScaleMode::Fill => ScaleMode::Fit,
-                                    };
+                                Some(VirtualKeyCode::M) if hotkeys => {
+                                    self.scale_mode = self.scale_mode.next();
+                                    notification =
+                                        Some(format!("scale: {}", self.scale_mode.name()));
                                     self.filter.resize(
                                         &self.queue,
                                         PhysicalSize {
@@ -184,18 +240,50 @@ impl WindowImpl for Window {
                                         self.scale_mode,
                                     );
                                 }
-                                Some(VirtualKeyCode::R) => reset = true,
-                                Some(VirtualKeyCode::Key1) => new_filter = Some(1),
-                                Some(VirtualKeyCode::Key2) => new_filter = Some(2),
-                                Some(VirtualKeyCode::Key3) => new_filter = Some(3),
-                                Some(VirtualKeyCode::Key4) => new_filter = Some(4),
-                                Some(VirtualKeyCode::Key5) => new_filter = Some(5),
+                                Some(VirtualKeyCode::R) if hotkeys => reset = true,
+                                Some(VirtualKeyCode::F5) => save_state = true,
+                                Some(VirtualKeyCode::F6) => load_state = true,
+                                Some(VirtualKeyCode::F7) => screenshot = true,
+                                Some(VirtualKeyCode::F8) => toggle_gif_recording = true,
+                                Some(VirtualKeyCode::P) if hotkeys => pause = true,
+                                Some(VirtualKeyCode::N) if hotkeys => step = true,
+                                Some(VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract)
+                                    if hotkeys =>
+                                {
+                                    slower = true
+                                }
+                                Some(
+                                    VirtualKeyCode::Equals
+                                    | VirtualKeyCode::Plus
+                                    | VirtualKeyCode::NumpadAdd,
+                                ) if hotkeys => faster = true,
+                                Some(VirtualKeyCode::F9) => toggle_inspector = true,
+                                Some(VirtualKeyCode::F10) => next_inspector_range = true,
+                                Some(VirtualKeyCode::F11) => toggle_debug_overlay = true,
+                                Some(VirtualKeyCode::PageUp) => inspector_scroll -= 1,
+                                Some(VirtualKeyCode::PageDown) => inspector_scroll += 1,
+                                Some(VirtualKeyCode::Key1) if hotkeys => new_filter = Some(1),
+                                Some(VirtualKeyCode::Key2) if hotkeys => new_filter = Some(2),
+                                Some(VirtualKeyCode::Key3) if hotkeys => new_filter = Some(3),
+                                Some(VirtualKeyCode::Key4) if hotkeys => new_filter = Some(4),
+                                Some(VirtualKeyCode::Key5) if hotkeys => new_filter = Some(5),
+                                Some(VirtualKeyCode::Key6) if hotkeys && self.shader.is_some() => {
+                                    new_filter = Some(6)
+                                }
                                 _ => (),
                             }
+                        }
 
-                            self.gamepads[0] |= gamepad_button(&input);
-                        } else {
-                            self.gamepads[0] &= !gamepad_button(&input);
+                        let buttons = input
+                            .virtual_keycode
+                            .and_then(|key| self.key_bindings.buttons(&format!("{:?}", key)))
+                            .unwrap_or_else(|| vec![(0, gamepad_button(&input))]);
+                        for (gamepad, mask) in buttons {
+                            if input.state == ElementState::Pressed {
+                                self.gamepads[gamepad] |= mask;
+                            } else {
+                                self.gamepads[gamepad] &= !mask;
+                            }
                         }
                     }
                     _ => (),
@@ -211,6 +299,7 @@ impl WindowImpl for Window {
                 _ => (),
             }
             if let Some(new_filter) = new_filter {
+                notification = Some(format!("filter: {}", filter_name(new_filter)));
                 self.filter = create_filter(
                     &self.device,
                     &self.palette_screen_mode.screen_view,
@@ -218,12 +307,35 @@ impl WindowImpl for Window {
                     self.surface_config.format,
                     new_filter,
                     self.scale_mode,
+                    self.shader.as_deref(),
                 );
             }
         });
+        let size = self.window.inner_size();
         Input {
             gamepads: self.gamepads,
+            mouse_position: self.scale_mode.window_to_screen(
+                size.width as f32,
+                size.height as f32,
+                self.cursor_position.x as f32,
+                self.cursor_position.y as f32,
+            ),
+            mouse_buttons: self.mouse_buttons,
+            text: std::mem::take(&mut self.text),
             reset,
+            save_state,
+            load_state,
+            pause,
+            step,
+            slower,
+            faster,
+            toggle_inspector,
+            next_inspector_range,
+            inspector_scroll,
+            screenshot,
+            toggle_gif_recording,
+            toggle_debug_overlay,
+            notification,
         }
     }
 
@@ -232,6 +344,7 @@ impl WindowImpl for Window {
         self.palette_screen_mode
             .write_framebuffer(&self.queue, framebuffer);
         self.palette_screen_mode.write_palette(&self.queue, palette);
+        self.filter.prepare(&self.device, &self.queue);
 
         let output = self.surface.get_current_texture().unwrap();
         let view = output
@@ -269,11 +382,36 @@ impl WindowImpl for Window {
         output.present();
     }
 
+    fn set_title(&mut self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    fn set_text_input(&mut self, text_input: bool) {
+        self.text_input = text_input;
+    }
+
     fn is_open(&self) -> bool {
         self.is_open
     }
 }
 
+/// A 32x32 icon showing "W8" in the small overlay font.
+fn window_icon() -> Option<Icon> {
+    let mut rgba = [0x20, 0x20, 0x28, 0xff].repeat(32 * 32);
+    for (index, c) in "W8".chars().enumerate() {
+        let glyph = small_font::glyph(c);
+        for y in 0..20 {
+            for x in 0..12 {
+                if glyph & (1 << (14 - (y / 4 * 3 + x / 4))) != 0 {
+                    let offset = ((6 + y) * 32 + 2 + index * 16 + x) * 4;
+                    rgba[offset..offset + 4].copy_from_slice(&[0xff, 0x9c, 0x2a, 0xff]);
+                }
+            }
+        }
+    }
+    Icon::from_rgba(rgba, 32, 32).ok()
+}
+
 fn create_filter(
     device: &wgpu::Device,
     screen_texture: &wgpu::TextureView,
@@ -281,6 +419,7 @@ fn create_filter(
     surface_format: wgpu::TextureFormat,
     filter: u32,
     scale_mode: ScaleMode,
+    shader: Option<&Path>,
 ) -> Box<dyn Filter> {
     match filter {
         1 => Box::new(SquareFilter::new(
@@ -313,6 +452,14 @@ fn create_filter(
             true,
             scale_mode,
         )),
+        6 if shader.is_some() => Box::new(CustomFilter::new(
+            device,
+            screen_texture,
+            window_size,
+            surface_format,
+            scale_mode,
+            shader.unwrap(),
+        )),
         _ => Box::new(AutoCrtFilter::new(
             device,
             screen_texture,
@@ -325,6 +472,8 @@ fn create_filter(
 
 trait Filter {
     fn resize(&mut self, queue: &wgpu::Queue, new_size: PhysicalSize<u32>, scale_mode: ScaleMode);
+    /// Called before each frame is rendered.
+    fn prepare(&mut self, _device: &wgpu::Device, _queue: &wgpu::Queue) {}
     fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>);
 }
 
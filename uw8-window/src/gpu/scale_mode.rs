@@ -1,9 +1,17 @@
+use anyhow::{bail, Result};
 use winit::dpi::PhysicalSize;
 
 #[derive(Debug, Copy, Clone)]
 pub enum ScaleMode {
+    /// Scale to fit the window, keeping the aspect ratio.
     Fit,
+    /// Scale to fill the window, keeping the aspect ratio and cropping the screen.
     Fill,
+    /// Scale by the largest whole multiple that fits the window, so that all screen
+    /// pixels have the same size.
+    Integer,
+    /// Stretch to the window, ignoring the aspect ratio.
+    Stretch,
 }
 
 impl Default for ScaleMode {
@@ -13,16 +21,196 @@ impl Default for ScaleMode {
 }
 
 impl ScaleMode {
-    pub fn texture_scale_from_resolution(&self, res: PhysicalSize<u32>) -> [f32; 4] {
-        let scale = match self {
-            ScaleMode::Fit => ((res.width as f32) / 160.0).min((res.height as f32) / 120.0),
-            ScaleMode::Fill => ((res.width as f32) / 160.0).max((res.height as f32) / 120.0),
-        };
-        [
-            scale / res.width as f32,
-            scale / res.height as f32,
-            2.0 / scale,
-            0.0,
-        ]
+    pub fn from_name(name: &str) -> Result<ScaleMode> {
+        Ok(match name {
+            "fit" => ScaleMode::Fit,
+            "fill" => ScaleMode::Fill,
+            "integer" => ScaleMode::Integer,
+            "stretch" => ScaleMode::Stretch,
+            _ => bail!(
+                "Unknown scale mode '{}', expected fit, fill, integer or stretch",
+                name
+            ),
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScaleMode::Fit => "fit",
+            ScaleMode::Fill => "fill",
+            ScaleMode::Integer => "integer",
+            ScaleMode::Stretch => "stretch",
+        }
+    }
+
+    /// The mode the M hotkey switches to.
+    pub fn next(&self) -> ScaleMode {
+        match self {
+            ScaleMode::Fit => ScaleMode::Fill,
+            ScaleMode::Fill => ScaleMode::Integer,
+            ScaleMode::Integer => ScaleMode::Stretch,
+            ScaleMode::Stretch => ScaleMode::Fit,
+        }
+    }
+
+    /// Twice the size of a screen pixel in window pixels, horizontally and vertically.
+    fn scale(&self, width: f32, height: f32) -> (f32, f32) {
+        match self {
+            ScaleMode::Fit => {
+                let scale = (width / 160.0).min(height / 120.0);
+                (scale, scale)
+            }
+            ScaleMode::Fill => {
+                let scale = (width / 160.0).max(height / 120.0);
+                (scale, scale)
+            }
+            ScaleMode::Integer => {
+                let scale = (width / 320.0).min(height / 240.0).floor().max(1.0) * 2.0;
+                (scale, scale)
+            }
+            ScaleMode::Stretch => (width / 160.0, height / 120.0),
+        }
+    }
+
+    /// The position of the top left corner of the centered screen in the window and
+    /// the size of a screen pixel in window pixels. In integer mode, the screen is
+    /// aligned to whole window pixels.
+    pub fn screen_rect(&self, width: f32, height: f32) -> (f32, f32, f32, f32) {
+        let (scale_x, scale_y) = self.scale(width, height);
+        let x = (width - scale_x * 160.0) / 2.0;
+        let y = (height - scale_y * 120.0) / 2.0;
+        match self {
+            ScaleMode::Integer => (x.floor(), y.floor(), scale_x / 2.0, scale_y / 2.0),
+            _ => (x, y, scale_x / 2.0, scale_y / 2.0),
+        }
+    }
+
+    pub fn uniforms_from_resolution(&self, res: PhysicalSize<u32>) -> Uniforms {
+        let (width, height) = (res.width as f32, res.height as f32);
+        let (scale_x, scale_y) = self.scale(width, height);
+        let (x, y, _, _) = self.screen_rect(width, height);
+        Uniforms {
+            texture_scale: [
+                scale_x / width,
+                scale_y / height,
+                2.0 / scale_x,
+                2.0 / scale_y,
+            ],
+            // the offset of the screen from the window center in clip space, which
+            // points up instead of down
+            screen_offset: [
+                (x - (width - scale_x * 160.0) / 2.0) * 2.0 / width,
+                (y - (height - scale_y * 120.0) / 2.0) * -2.0 / height,
+                0.0,
+                0.0,
+            ],
+        }
+    }
+
+    /// Converts a position in a window of the given size to 320x240 screen coordinates,
+    /// taking the centering and scaling of the screen into account.
+    pub fn window_to_screen(&self, width: f32, height: f32, x: f32, y: f32) -> (i16, i16) {
+        let (left, top, pixel_width, pixel_height) = self.screen_rect(width, height);
+        let x = (x - left) / pixel_width;
+        let y = (y - top) / pixel_height;
+        (x.floor() as i16, y.floor() as i16)
+    }
+}
+
+/// The uniforms shared by all filters: the scale of the screen in clip space and the
+/// size of a window pixel in screen pixels (`texture_scale`) and the offset of the
+/// screen in clip space (`screen_offset`).
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Uniforms {
+    texture_scale: [f32; 4],
+    screen_offset: [f32; 4],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        let mut mode = ScaleMode::Fit;
+        for _ in 0..4 {
+            assert_eq!(
+                ScaleMode::from_name(mode.name()).unwrap().name(),
+                mode.name()
+            );
+            mode = mode.next();
+        }
+        assert_eq!(mode.name(), "fit");
+        assert!(ScaleMode::from_name("zoom").is_err());
+    }
+
+    #[test]
+    fn screen_rect_centers_screen() {
+        assert_eq!(
+            ScaleMode::Fit.screen_rect(640.0, 480.0),
+            (0.0, 0.0, 2.0, 2.0)
+        );
+        assert_eq!(
+            ScaleMode::Fit.screen_rect(800.0, 480.0),
+            (80.0, 0.0, 2.0, 2.0)
+        );
+        assert_eq!(
+            ScaleMode::Fill.screen_rect(640.0, 240.0),
+            (0.0, -120.0, 2.0, 2.0)
+        );
+        assert_eq!(
+            ScaleMode::Stretch.screen_rect(320.0, 480.0),
+            (0.0, 0.0, 1.0, 2.0)
+        );
+    }
+
+    #[test]
+    fn screen_rect_integer_uses_whole_pixels() {
+        assert_eq!(
+            ScaleMode::Integer.screen_rect(700.0, 500.0),
+            (30.0, 10.0, 2.0, 2.0)
+        );
+        assert_eq!(
+            ScaleMode::Integer.screen_rect(641.0, 480.0),
+            (0.0, 0.0, 2.0, 2.0)
+        );
+        // never smaller than one window pixel per screen pixel
+        assert_eq!(
+            ScaleMode::Integer.screen_rect(100.0, 100.0),
+            (-110.0, -70.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn window_to_screen_maps_to_screen_pixels() {
+        let mode = ScaleMode::Fit;
+        assert_eq!(mode.window_to_screen(800.0, 480.0, 80.0, 0.0), (0, 0));
+        assert_eq!(mode.window_to_screen(800.0, 480.0, 79.0, 1.5), (-1, 0));
+        assert_eq!(
+            mode.window_to_screen(800.0, 480.0, 719.9, 479.9),
+            (319, 239)
+        );
+        assert_eq!(
+            mode.window_to_screen(800.0, 480.0, 720.0, 480.0),
+            (320, 240)
+        );
+        assert_eq!(
+            ScaleMode::Stretch.window_to_screen(320.0, 480.0, 10.0, 10.0),
+            (10, 5)
+        );
+    }
+
+    #[test]
+    fn uniforms_offset_only_for_integer_alignment() {
+        let res = |width, height| PhysicalSize::new(width, height);
+        let uniforms = ScaleMode::Fit.uniforms_from_resolution(res(800, 480));
+        assert_eq!(uniforms.texture_scale, [4.0 / 800.0, 4.0 / 480.0, 0.5, 0.5]);
+        assert_eq!(uniforms.screen_offset, [0.0, 0.0, 0.0, 0.0]);
+        let uniforms = ScaleMode::Integer.uniforms_from_resolution(res(641, 481));
+        assert_eq!(
+            uniforms.screen_offset,
+            [-1.0 / 641.0, 1.0 / 481.0, 0.0, 0.0]
+        );
     }
 }
@@ -17,9 +17,7 @@ impl SquareFilter {
         surface_format: wgpu::TextureFormat,
         scale_mode: ScaleMode,
     ) -> SquareFilter {
-        let uniforms = Uniforms {
-            texture_scale: scale_mode.texture_scale_from_resolution(resolution),
-        };
+        let uniforms = scale_mode.uniforms_from_resolution(resolution);
 
         let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
@@ -128,9 +126,7 @@ impl SquareFilter {
 
 impl Filter for SquareFilter {
     fn resize(&mut self, queue: &wgpu::Queue, new_size: PhysicalSize<u32>, scale_mode: ScaleMode) {
-        let uniforms = Uniforms {
-            texture_scale: scale_mode.texture_scale_from_resolution(new_size),
-        };
+        let uniforms = scale_mode.uniforms_from_resolution(new_size);
         queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
     }
 
@@ -140,9 +136,3 @@ impl Filter for SquareFilter {
         render_pass.draw(0..6, 0..1);
     }
 }
-
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct Uniforms {
-    texture_scale: [f32; 4],
-}
@@ -5,6 +5,7 @@ struct VertexOutput {
 
 struct Uniforms {
     texture_scale: vec4<f32>,
+    screen_offset: vec4<f32>,
 }
 
 @group(0) @binding(2) var<uniform> uniforms: Uniforms;
@@ -17,7 +18,7 @@ fn vs_main(
     let i = in_vertex_index / 3u + in_vertex_index % 3u;
     let x = 0.0 + f32(i % 2u) * 320.0;
     let y = 0.0 + f32(i / 2u) * 240.0;
-    out.clip_position = vec4<f32>((vec2<f32>(x, y) - vec2<f32>(160.0, 120.0)) * uniforms.texture_scale.xy, 0.0, 1.0);
+    out.clip_position = vec4<f32>((vec2<f32>(x, y) - vec2<f32>(160.0, 120.0)) * uniforms.texture_scale.xy + uniforms.screen_offset.xy, 0.0, 1.0);
     out.tex_coords = vec2<f32>(x, y);
     return out;
 }
@@ -25,9 +26,9 @@ fn vs_main(
 @group(0) @binding(0) var screen_texture: texture_2d<f32>;
 @group(0) @binding(1) var linear_sampler: sampler;
 
-fn aa_tex_coord(c: f32) -> f32 {
-    let low = c - uniforms.texture_scale.z * 0.5;
-    let high = c + uniforms.texture_scale.z * 0.5;
+fn aa_tex_coord(c: f32, footprint: f32) -> f32 {
+    let low = c - footprint * 0.5;
+    let high = c + footprint * 0.5;
     let base = floor(low);
     let center = base + 0.5;
     let next = base + 1.0;
@@ -40,5 +41,5 @@ fn aa_tex_coord(c: f32) -> f32 {
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(screen_texture, linear_sampler, vec2<f32>(aa_tex_coord(in.tex_coords.x), aa_tex_coord(in.tex_coords.y)) / vec2<f32>(320.0, 240.0));
+    return textureSample(screen_texture, linear_sampler, vec2<f32>(aa_tex_coord(in.tex_coords.x, uniforms.texture_scale.z), aa_tex_coord(in.tex_coords.y, uniforms.texture_scale.w)) / vec2<f32>(320.0, 240.0));
 }
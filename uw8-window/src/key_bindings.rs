@@ -0,0 +1,201 @@
+use anyhow::{anyhow, bail, Result};
+use std::path::Path;
+
+/// The button names in the order of the bits of a gamepad byte.
+const BUTTON_NAMES: [&str; 8] = ["up", "down", "left", "right", "a", "b", "x", "y"];
+
+/// The keys used as hotkeys by the runtime, which can't be bound to buttons. Both the
+/// winit and the minifb names are listed where they differ.
+const RESERVED_KEYS: [&str; 29] = [
+    "escape",
+    "f",
+    "m",
+    "r",
+    "p",
+    "n",
+    "1",
+    "2",
+    "3",
+    "4",
+    "5",
+    "6",
+    "minus",
+    "numpadsubtract",
+    "numpadminus",
+    "equals",
+    "equal",
+    "plus",
+    "numpadadd",
+    "numpadplus",
+    "f5",
+    "f6",
+    "f7",
+    "f8",
+    "f9",
+    "f10",
+    "f11",
+    "pageup",
+    "pagedown",
+];
+
+/// Maps keyboard keys to buttons of the four gamepads.
+///
+/// A binding is written as `KEY=BUTTON` for gamepad 0 or `KEY=GAMEPAD:BUTTON`, eg.
+/// `W=1:up`. Keys are named like winit's `VirtualKeyCode` (`Up`, `Z`, `Key1`,
+/// `Space`, `LShift`, `Numpad8`, ...), ignoring case. The bindings override the
+/// default keyboard layout only for the keys they bind, `KEY=none` removes the
+/// default of a key.
+#[derive(Debug, Clone, Default)]
+pub struct KeyBindings {
+    bindings: Vec<(String, usize, u8)>,
+}
+
+impl KeyBindings {
+    pub fn add(&mut self, binding: &str) -> Result<()> {
+        let (key, target) = binding
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid key binding '{}', expected KEY=BUTTON", binding))?;
+        let (gamepad, button) = match target.split_once(':') {
+            Some((gamepad, button)) => (gamepad.trim().parse::<usize>()?, button),
+            None => (0, target),
+        };
+        if gamepad > 3 {
+            bail!("Invalid gamepad {} in key binding '{}'", gamepad, binding);
+        }
+        let key = canonical_key_name(key.trim());
+        if RESERVED_KEYS.contains(&key.as_str()) {
+            bail!(
+                "Key '{}' in key binding '{}' is reserved for a runtime hotkey",
+                key,
+                binding
+            );
+        }
+        let button = button.trim().to_ascii_lowercase();
+        let mask = if button == "none" {
+            0
+        } else if let Some(bit) = BUTTON_NAMES.iter().position(|&name| name == button) {
+            1 << bit
+        } else {
+            bail!(
+                "Unknown button '{}' in key binding '{}', expected one of {} or none",
+                button,
+                binding,
+                BUTTON_NAMES.join(", ")
+            );
+        };
+        self.bindings.push((key, gamepad, mask));
+        Ok(())
+    }
+
+    /// Adds the bindings from a file with one binding per line. Empty lines and lines
+    /// starting with `#` are ignored.
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        for line in std::fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                self.add(line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The gamepads and button masks bound to the key with the given name or `None`
+    /// if the key isn't bound and keeps its default.
+    pub(crate) fn buttons(&self, key: &str) -> Option<Vec<(usize, u8)>> {
+        let key = canonical_key_name(key);
+        let buttons: Vec<_> = self
+            .bindings
+            .iter()
+            .filter(|(name, _, _)| *name == key)
+            .map(|&(_, gamepad, mask)| (gamepad, mask))
+            .collect();
+        (!buttons.is_empty()).then_some(buttons)
+    }
+}
+
+/// Normalizes the key names of winit and minifb, which differ for a few keys.
+fn canonical_key_name(name: &str) -> String {
+    let name = name.to_ascii_lowercase();
+    let name = match name.strip_prefix("key") {
+        Some(rest) if rest.len() == 1 => rest,
+        _ => &name,
+    };
+    match name {
+        "leftshift" => "lshift",
+        "rightshift" => "rshift",
+        "leftctrl" | "lctrl" => "lcontrol",
+        "rightctrl" | "rctrl" => "rcontrol",
+        "leftalt" => "lalt",
+        "rightalt" => "ralt",
+        "enter" => "return",
+        name => name,
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bindings(bindings: &[&str]) -> Result<KeyBindings> {
+        let mut key_bindings = KeyBindings::default();
+        for binding in bindings {
+            key_bindings.add(binding)?;
+        }
+        Ok(key_bindings)
+    }
+
+    #[test]
+    fn add_defaults_to_gamepad_0() {
+        let key_bindings = bindings(&["Space=a"]).unwrap();
+        assert_eq!(key_bindings.buttons("space").unwrap(), [(0, 16)]);
+    }
+
+    #[test]
+    fn add_parses_gamepad_and_ignores_case() {
+        let key_bindings = bindings(&["W = 1:Up", "KeyW=1:y"]).unwrap();
+        assert_eq!(key_bindings.buttons("W").unwrap(), [(1, 1), (1, 128)]);
+    }
+
+    #[test]
+    fn add_normalizes_key_names() {
+        let key_bindings = bindings(&["LeftShift=2:b"]).unwrap();
+        assert_eq!(key_bindings.buttons("LShift").unwrap(), [(2, 32)]);
+    }
+
+    #[test]
+    fn unbound_keys_keep_their_default() {
+        let key_bindings = bindings(&["W=1:up"]).unwrap();
+        assert_eq!(key_bindings.buttons("Z"), None);
+    }
+
+    #[test]
+    fn add_accepts_none_to_remove_the_default() {
+        let key_bindings = bindings(&["Z=none"]).unwrap();
+        assert_eq!(key_bindings.buttons("Z"), Some(vec![(0, 0)]));
+    }
+
+    #[test]
+    fn add_rejects_invalid_bindings() {
+        assert!(bindings(&["W"]).is_err());
+        assert!(bindings(&["W=4:up"]).is_err());
+        assert!(bindings(&["W=x:up"]).is_err());
+        assert!(bindings(&["W=start"]).is_err());
+    }
+
+    #[test]
+    fn add_rejects_reserved_keys() {
+        for key in [
+            "R",
+            "p",
+            "Key1",
+            "F5",
+            "Escape",
+            "NumpadAdd",
+            "Equal",
+            "PageUp",
+        ] {
+            assert!(bindings(&[&format!("{}=a", key)]).is_err(), "{}", key);
+        }
+    }
+}
@@ -1,37 +1,54 @@
 use anyhow::Result;
+use gamepad::Gamepads;
 use gpu::scale_mode::ScaleMode;
+use overlay::Overlay;
+use std::path::PathBuf;
 use std::time::Instant;
 
 mod cpu;
+mod gamepad;
 mod gpu;
+mod key_bindings;
+mod overlay;
+mod small_font;
+mod software_filter;
+
+pub use key_bindings::KeyBindings;
 
 pub struct Window {
     inner: Box<dyn WindowImpl>,
-    fps_counter: Option<FpsCounter>,
+    fps_counter: FpsCounter,
+    print_fps: bool,
+    title: String,
+    gamepads: Gamepads,
+    overlay: Overlay,
 }
 
 struct FpsCounter {
     start: Instant,
     num_frames: u32,
+    fps: Option<f32>,
 }
 
 impl Window {
     pub fn new(mut config: WindowConfig) -> Result<Window> {
-        let fps_counter = if config.fps_counter {
-            Some(FpsCounter {
-                start: Instant::now(),
-                num_frames: 0,
-            })
-        } else {
-            None
+        let fps_counter = FpsCounter {
+            start: Instant::now(),
+            num_frames: 0,
+            fps: None,
         };
+        let print_fps = config.fps_counter;
         config.scale = config.scale.max(1.).min(20.);
         if config.enable_gpu {
-            match gpu::Window::new(config) {
+            match gpu::Window::new(config.clone()) {
                 Ok(window) => {
                     return Ok(Window {
                         inner: Box::new(window),
                         fps_counter,
+                        print_fps,
+                        title: "MicroW8".to_string(),
+                        gamepads: Gamepads::new(),
+                        overlay: Overlay::new(),
                     })
                 }
                 Err(err) => eprintln!(
@@ -40,25 +57,75 @@ impl Window {
                 ),
             }
         }
-        cpu::Window::new().map(|window| Window {
+        cpu::Window::new(config).map(|window| Window {
             inner: Box::new(window),
             fps_counter,
+            print_fps,
+            title: "MicroW8".to_string(),
+            gamepads: Gamepads::new(),
+            overlay: Overlay::new(),
         })
     }
 
     pub fn begin_frame(&mut self) -> Input {
-        self.inner.begin_frame()
+        let mut input = self.inner.begin_frame();
+        let (gamepads, reset) = self.gamepads.poll();
+        for (input, gamepad) in input.gamepads.iter_mut().zip(gamepads) {
+            *input |= gamepad;
+        }
+        input.reset |= reset;
+        if input.toggle_debug_overlay {
+            self.overlay.next_mode();
+        }
+        if let Some(message) = input.notification.take() {
+            self.overlay.notify(message);
+        }
+        self.overlay.set_mouse_position(input.mouse_position);
+        input
+    }
+
+    /// Sets the font shown by the debug overlay.
+    pub fn set_font(&mut self, font: &[u8]) {
+        self.overlay.set_font(font);
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        if title != self.title {
+            self.title = title.to_string();
+            self.inner.set_title(title);
+        }
+    }
+
+    /// Sets whether the cart reads typed text. The hotkeys on character keys then only
+    /// work together with Ctrl, so they don't fire while typing.
+    pub fn set_text_input(&mut self, text_input: bool) {
+        self.inner.set_text_input(text_input);
+    }
+
+    /// Shows a message over the screen for a short time.
+    pub fn notify(&mut self, message: impl Into<String>) {
+        self.overlay.notify(message.into());
+    }
+
+    /// The frame rate measured over the last second.
+    pub fn fps(&self) -> Option<f32> {
+        self.fps_counter.fps
     }
+
     pub fn end_frame(&mut self, framebuffer: &[u8], palette: &[u8], next_frame: Instant) {
+        let (framebuffer, palette) = self.overlay.compose(framebuffer, palette);
         self.inner.end_frame(framebuffer, palette, next_frame);
-        if let Some(ref mut fps_counter) = self.fps_counter {
-            fps_counter.num_frames += 1;
-            let elapsed = fps_counter.start.elapsed().as_secs_f32();
-            if elapsed >= 1.0 {
-                println!("fps: {:.1}", fps_counter.num_frames as f32 / elapsed);
-                fps_counter.num_frames = 0;
-                fps_counter.start = Instant::now();
+        let fps_counter = &mut self.fps_counter;
+        fps_counter.num_frames += 1;
+        let elapsed = fps_counter.start.elapsed().as_secs_f32();
+        if elapsed >= 1.0 {
+            let fps = fps_counter.num_frames as f32 / elapsed;
+            if self.print_fps {
+                println!("fps: {:.1}", fps);
             }
+            fps_counter.fps = Some(fps);
+            fps_counter.num_frames = 0;
+            fps_counter.start = Instant::now();
         }
     }
 
@@ -67,7 +134,7 @@ impl Window {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WindowConfig {
     enable_gpu: bool,
     filter: u32,
@@ -75,6 +142,8 @@ pub struct WindowConfig {
     fps_counter: bool,
     scale: f32,
     scale_mode: ScaleMode,
+    key_bindings: KeyBindings,
+    shader: Option<PathBuf>,
 }
 
 impl Default for WindowConfig {
@@ -86,6 +155,8 @@ impl Default for WindowConfig {
             fps_counter: false,
             scale: 2.,
             scale_mode: ScaleMode::Fit,
+            key_bindings: KeyBindings::default(),
+            shader: None,
         }
     }
 }
@@ -93,6 +164,10 @@ impl Default for WindowConfig {
 impl WindowConfig {
     pub fn parse_arguments(&mut self, args: &mut pico_args::Arguments) {
         self.enable_gpu = !args.contains("--no-gpu");
+        self.shader = args.opt_value_from_str("--shader").unwrap();
+        if self.shader.is_some() {
+            self.filter = 6;
+        }
         if let Some(filter) = args.opt_value_from_str::<_, String>("--filter").unwrap() {
             self.filter = match filter.as_str() {
                 "1" | "nearest" => 1,
@@ -115,16 +190,87 @@ impl WindowConfig {
         if args.contains("--scale-fill") {
             self.scale_mode = ScaleMode::Fill;
         }
+        if let Some(mode) = args
+            .opt_value_from_str::<_, String>("--scale-mode")
+            .unwrap()
+        {
+            self.scale_mode = match ScaleMode::from_name(&mode) {
+                Ok(mode) => mode,
+                Err(err) => {
+                    println!("{}", err);
+                    std::process::exit(1);
+                }
+            };
+        }
+        let key_files = args
+            .values_from_str::<_, String>("--keys")
+            .unwrap_or_else(|err| {
+                println!("Invalid --keys: {}", err);
+                std::process::exit(1);
+            });
+        for path in key_files {
+            if let Err(err) = self.key_bindings.load(std::path::Path::new(&path)) {
+                println!("Failed to load key bindings from '{}': {}", path, err);
+                std::process::exit(1);
+            }
+        }
+        let bindings = args
+            .values_from_str::<_, String>("--bind")
+            .unwrap_or_else(|err| {
+                println!("Invalid --bind: {}", err);
+                std::process::exit(1);
+            });
+        for binding in bindings {
+            if let Err(err) = self.key_bindings.add(&binding) {
+                println!("{}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// The name of an upscale filter as accepted by `--filter`.
+fn filter_name(filter: u32) -> &'static str {
+    match filter {
+        1 => "nearest",
+        2 => "fast_crt",
+        3 => "ss_crt",
+        4 => "chromatic",
+        6 => "custom",
+        _ => "auto_crt",
     }
 }
 
 pub struct Input {
     pub gamepads: [u8; 4],
+    /// The mouse position in screen pixels, can be outside of the screen.
+    pub mouse_position: (i16, i16),
+    /// The pressed mouse buttons, bit 0: left, bit 1: right, bit 2: middle.
+    pub mouse_buttons: u8,
+    /// The characters typed since the last frame.
+    pub text: String,
     pub reset: bool,
+    pub save_state: bool,
+    pub load_state: bool,
+    pub pause: bool,
+    pub step: bool,
+    pub slower: bool,
+    pub faster: bool,
+    pub toggle_inspector: bool,
+    pub next_inspector_range: bool,
+    pub inspector_scroll: i32,
+    pub screenshot: bool,
+    pub toggle_gif_recording: bool,
+    /// Handled by `Window` itself, cycles through the palette, font and magnifier views.
+    pub toggle_debug_overlay: bool,
+    /// A message about a change of the window settings, shown by `Window` itself.
+    notification: Option<String>,
 }
 
 trait WindowImpl {
     fn begin_frame(&mut self) -> Input;
     fn end_frame(&mut self, framebuffer: &[u8], palette: &[u8], next_frame: Instant);
+    fn set_title(&mut self, title: &str);
+    fn set_text_input(&mut self, text_input: bool);
     fn is_open(&self) -> bool;
 }
@@ -0,0 +1,263 @@
+use crate::small_font;
+use std::time::{Duration, Instant};
+
+/// Number of screen pixels on each side of the cursor shown by the magnifier.
+const MAGNIFIER_RADIUS: i32 = 4;
+const MAGNIFIER_CELL: i32 = 12;
+
+/// How long a notification is shown.
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Mode {
+    Off,
+    Palette,
+    Font,
+    Magnifier,
+}
+
+/// Draws debug views and notifications over the screen, which are composited into a
+/// copy of the framebuffer so that they work the same in the gpu and cpu window. As
+/// the overlay can only use the colors of the current palette, it is drawn in the
+/// darkest and brightest palette colors.
+pub struct Overlay {
+    mode: Mode,
+    notification: Option<(String, Instant)>,
+    font: Vec<u8>,
+    mouse_position: (i16, i16),
+    framebuffer: Vec<u8>,
+    palette: Vec<u8>,
+    black: u8,
+    white: u8,
+    uniform_palette: bool,
+}
+
+impl Overlay {
+    pub fn new() -> Overlay {
+        Overlay {
+            mode: Mode::Off,
+            notification: None,
+            font: vec![0; 2048],
+            mouse_position: (0, 0),
+            framebuffer: vec![0; 320 * 240],
+            palette: vec![0; 1024],
+            black: 0,
+            white: 0,
+            uniform_palette: false,
+        }
+    }
+
+    /// Switches to the next view: palette, font, magnifier and off again.
+    pub fn next_mode(&mut self) {
+        self.mode = match self.mode {
+            Mode::Off => Mode::Palette,
+            Mode::Palette => Mode::Font,
+            Mode::Font => Mode::Magnifier,
+            Mode::Magnifier => Mode::Off,
+        };
+    }
+
+    pub fn set_font(&mut self, font: &[u8]) {
+        let len = font.len().min(self.font.len());
+        self.font[..len].copy_from_slice(&font[..len]);
+    }
+
+    /// Shows a message at the bottom of the screen for a short time.
+    pub fn notify(&mut self, message: String) {
+        self.notification = Some((message, Instant::now()));
+    }
+
+    pub fn set_mouse_position(&mut self, position: (i16, i16)) {
+        self.mouse_position = position;
+    }
+
+    /// Returns the framebuffer with the overlay drawn over it and the palette to show it
+    /// with.
+    pub fn compose<'a>(
+        &'a mut self,
+        framebuffer: &'a [u8],
+        palette: &'a [u8],
+    ) -> (&'a [u8], &'a [u8]) {
+        if let Some((_, time)) = self.notification {
+            if time.elapsed() >= NOTIFICATION_DURATION {
+                self.notification = None;
+            }
+        }
+        if self.mode == Mode::Off && self.notification.is_none() {
+            return (framebuffer, palette);
+        }
+        self.palette.copy_from_slice(palette);
+        self.uniform_palette = palette
+            .chunks_exact(4)
+            .all(|color| color[..3] == palette[..3]);
+        if self.uniform_palette {
+            // all indices show the same color (eg. during a fade), so the screen can use
+            // index 0 only, leaving indices 1 and 2 for a black and white overlay
+            self.framebuffer.fill(0);
+            self.palette[4..8].copy_from_slice(&[0, 0, 0, 255]);
+            self.palette[8..12].copy_from_slice(&[255, 255, 255, 255]);
+            self.black = 1;
+            self.white = 2;
+        } else {
+            self.framebuffer.copy_from_slice(framebuffer);
+            let luminance = |index: &u8| {
+                let color = &palette[*index as usize * 4..][..3];
+                color[0] as u32 * 3 + color[1] as u32 * 6 + color[2] as u32
+            };
+            self.black = (0..=255).min_by_key(luminance).unwrap();
+            self.white = (0..=255).max_by_key(luminance).unwrap();
+        }
+        let (mouse_x, mouse_y) = (self.mouse_position.0 as i32, self.mouse_position.1 as i32);
+
+        match self.mode {
+            Mode::Off => (),
+            Mode::Palette => {
+                let hovered = self.draw_grid(mouse_x, mouse_y, |overlay, index, x, y| {
+                    let color = overlay.screen_index(index);
+                    overlay.fill_rect(x, y, 8, 8, color);
+                });
+                if let Some(index) = hovered {
+                    let color = &palette[index as usize * 4..][..3];
+                    self.draw_hex(4, 152, index as u32, 2);
+                    let rgb = (color[0] as u32) << 16 | (color[1] as u32) << 8 | color[2] as u32;
+                    self.draw_hex(16, 152, rgb, 6);
+                }
+            }
+            Mode::Font => {
+                let hovered = self.draw_grid(mouse_x, mouse_y, |overlay, index, x, y| {
+                    for row in 0..8 {
+                        let bits = overlay.font[index as usize * 8 + row as usize];
+                        for column in 0..8 {
+                            let color = if (bits << column) & 128 != 0 {
+                                overlay.white
+                            } else {
+                                overlay.black
+                            };
+                            overlay.set_pixel(x + column, y + row, color);
+                        }
+                    }
+                });
+                if let Some(index) = hovered {
+                    self.draw_hex(4, 152, index as u32, 2);
+                }
+            }
+            Mode::Magnifier => self.draw_magnifier(framebuffer, palette, mouse_x, mouse_y),
+        }
+        if let Some((message, _)) = self.notification.clone() {
+            let width = message.chars().count() as i32 * 8 - 2;
+            self.fill_rect(0, 240 - 18, width + 8, 18, self.black);
+            self.draw_text(4, 240 - 14, &message, 2, self.white);
+        }
+        (&self.framebuffer, &self.palette)
+    }
+
+    /// The index to draw a color of the cart palette with.
+    fn screen_index(&self, index: u8) -> u8 {
+        if self.uniform_palette {
+            0
+        } else {
+            index
+        }
+    }
+
+    /// Draws 16x16 cells of 8x8 pixels in the top left corner and returns the index of
+    /// the cell under the mouse.
+    fn draw_grid(
+        &mut self,
+        mouse_x: i32,
+        mouse_y: i32,
+        mut draw_cell: impl FnMut(&mut Overlay, u8, i32, i32),
+    ) -> Option<u8> {
+        self.fill_rect(0, 0, 153, 162, self.black);
+        let mut hovered = None;
+        for index in 0..=255u8 {
+            let x = 4 + (index as i32 % 16) * 9;
+            let y = 4 + (index as i32 / 16) * 9;
+            draw_cell(self, index, x, y);
+            if (x..x + 8).contains(&mouse_x) && (y..y + 8).contains(&mouse_y) {
+                hovered = Some(index);
+                self.draw_frame(x - 1, y - 1, 10, 10, self.white);
+            }
+        }
+        hovered
+    }
+
+    /// Shows the pixels around the mouse with their color indices, in the corner
+    /// farthest from the mouse.
+    fn draw_magnifier(&mut self, framebuffer: &[u8], palette: &[u8], mouse_x: i32, mouse_y: i32) {
+        let size = (MAGNIFIER_RADIUS * 2 + 1) * MAGNIFIER_CELL;
+        let left = if mouse_x < 160 { 320 - size - 4 } else { 4 };
+        let top = if mouse_y < 120 { 240 - size - 4 } else { 4 };
+        self.fill_rect(left - 2, top - 2, size + 4, size + 4, self.black);
+        for dy in -MAGNIFIER_RADIUS..=MAGNIFIER_RADIUS {
+            for dx in -MAGNIFIER_RADIUS..=MAGNIFIER_RADIUS {
+                let x = left + (dx + MAGNIFIER_RADIUS) * MAGNIFIER_CELL;
+                let y = top + (dy + MAGNIFIER_RADIUS) * MAGNIFIER_CELL;
+                let (screen_x, screen_y) = (mouse_x + dx, mouse_y + dy);
+                if !(0..320).contains(&screen_x) || !(0..240).contains(&screen_y) {
+                    continue;
+                }
+                let index = framebuffer[(screen_x + screen_y * 320) as usize];
+                let color_index = self.screen_index(index);
+                self.fill_rect(x, y, MAGNIFIER_CELL, MAGNIFIER_CELL, color_index);
+                let color = &palette[index as usize * 4..][..3];
+                let is_bright = color[0] as u32 * 3 + color[1] as u32 * 6 + color[2] as u32 > 1280;
+                let text_color = if is_bright { self.black } else { self.white };
+                self.draw_hex_in(x + 2, y + 4, index as u32, 2, text_color);
+            }
+        }
+        let center = left + MAGNIFIER_RADIUS * MAGNIFIER_CELL;
+        let middle = top + MAGNIFIER_RADIUS * MAGNIFIER_CELL;
+        self.draw_frame(
+            center - 1,
+            middle - 1,
+            MAGNIFIER_CELL + 2,
+            MAGNIFIER_CELL + 2,
+            self.white,
+        );
+    }
+
+    fn draw_hex(&mut self, x: i32, y: i32, value: u32, digits: i32) {
+        self.fill_rect(x - 1, y - 1, digits * 4 + 1, 7, self.black);
+        self.draw_hex_in(x, y, value, digits, self.white);
+    }
+
+    fn draw_hex_in(&mut self, x: i32, y: i32, value: u32, digits: i32, color: u8) {
+        let text = format!("{:01$X}", value, digits as usize);
+        self.draw_text(x, y, &text, 1, color);
+    }
+
+    fn draw_text(&mut self, x: i32, y: i32, text: &str, scale: i32, color: u8) {
+        for (index, c) in text.chars().enumerate() {
+            let glyph = small_font::glyph(c);
+            let left = x + index as i32 * 4 * scale;
+            for bit in 0..15 {
+                if glyph & (1 << (14 - bit)) != 0 {
+                    let (column, row) = (bit % 3, bit / 3);
+                    self.fill_rect(left + column * scale, y + row * scale, scale, scale, color);
+                }
+            }
+        }
+    }
+
+    fn draw_frame(&mut self, x: i32, y: i32, width: i32, height: i32, color: u8) {
+        self.fill_rect(x, y, width, 1, color);
+        self.fill_rect(x, y + height - 1, width, 1, color);
+        self.fill_rect(x, y, 1, height, color);
+        self.fill_rect(x + width - 1, y, 1, height, color);
+    }
+
+    fn fill_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: u8) {
+        for y in y..y + height {
+            for x in x..x + width {
+                self.set_pixel(x, y, color);
+            }
+        }
+    }
+
+    fn set_pixel(&mut self, x: i32, y: i32, color: u8) {
+        if (0..320).contains(&x) && (0..240).contains(&y) {
+            self.framebuffer[(x + y * 320) as usize] = color;
+        }
+    }
+}
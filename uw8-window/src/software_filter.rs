@@ -0,0 +1,172 @@
+use crate::gpu::scale_mode::ScaleMode;
+
+/// Width of the screen including a black border of one pixel on each side.
+const STRIDE: usize = 322;
+
+/// Scales the screen to the window in software for the cpu window, approximating the
+/// filters of the gpu window: filter 1 is the anti-aliased nearest filter, all crt
+/// filters are approximated by scanlines once a screen pixel is at least three window
+/// pixels high.
+pub struct SoftwareFilter {
+    screen: Vec<u32>,
+    pub filter: u32,
+}
+
+impl SoftwareFilter {
+    pub fn new(filter: u32) -> SoftwareFilter {
+        SoftwareFilter {
+            screen: vec![0; STRIDE * 242],
+            filter,
+        }
+    }
+
+    pub fn set_screen(&mut self, framebuffer: &[u8], palette: &[u8]) {
+        for (y, row) in framebuffer.chunks_exact(320).enumerate() {
+            let screen_row = &mut self.screen[(y + 1) * STRIDE + 1..][..320];
+            for (pixel, &color_index) in screen_row.iter_mut().zip(row) {
+                let offset = color_index as usize * 4;
+                *pixel = 0xff000000
+                    | ((palette[offset] as u32) << 16)
+                    | ((palette[offset + 1] as u32) << 8)
+                    | palette[offset + 2] as u32;
+            }
+        }
+    }
+
+    /// Renders the screen to a buffer of the window size.
+    pub fn render(
+        &self,
+        buffer: &mut Vec<u32>,
+        width: usize,
+        height: usize,
+        scale_mode: ScaleMode,
+    ) {
+        let (left, top, pixel_width, pixel_height) =
+            scale_mode.screen_rect(width as f32, height as f32);
+        let columns: Vec<Tap> = (0..width)
+            .map(|x| Tap::new(x, left, pixel_width, 320))
+            .collect();
+        let scanlines = self.filter != 1 && pixel_height >= 3.0;
+
+        buffer.resize(width * height, 0);
+        for (y, row) in buffer.chunks_exact_mut(width).enumerate() {
+            let (tap, brightness) = if scanlines {
+                let position = (y as f32 + 0.5 - top) / pixel_height;
+                let line = position.floor();
+                let distance = position - line - 0.5;
+                let index = border_index(line, 240);
+                let tap = Tap {
+                    a: index,
+                    b: index,
+                    weight: 0,
+                };
+                (
+                    tap,
+                    (1.15 * (1.0 - 2.2 * distance * distance) * 256.0) as u32,
+                )
+            } else {
+                (Tap::new(y, top, pixel_height, 240), 256)
+            };
+            let row0 = &self.screen[tap.a * STRIDE..][..STRIDE];
+            let row1 = &self.screen[tap.b * STRIDE..][..STRIDE];
+            for (pixel, column) in row.iter_mut().zip(&columns) {
+                let color0 = blend(row0[column.a], row0[column.b], column.weight);
+                let color1 = blend(row1[column.a], row1[column.b], column.weight);
+                let color = blend(color0, color1, tap.weight);
+                *pixel = if brightness == 256 {
+                    color
+                } else {
+                    scale_brightness(color, brightness)
+                };
+            }
+        }
+    }
+}
+
+/// The two neighbouring screen pixels covered by a window pixel, as indices into a row
+/// or column including the border, and the weight of the second one out of 256.
+struct Tap {
+    a: usize,
+    b: usize,
+    weight: u32,
+}
+
+impl Tap {
+    /// Blends the two pixels if the window pixel covers a pixel boundary, like
+    /// `aa_tex_coord` in square.wgsl.
+    fn new(position: usize, start: f32, pixel_size: f32, size: usize) -> Tap {
+        let low = (position as f32 - start) / pixel_size;
+        let high = (position as f32 + 1.0 - start) / pixel_size;
+        let base = low.floor();
+        let weight = if high > base + 1.0 {
+            (high - base - 1.0) / (high - low)
+        } else {
+            0.0
+        };
+        Tap {
+            a: border_index(base, size),
+            b: border_index(base + 1.0, size),
+            weight: (weight * 256.0) as u32,
+        }
+    }
+}
+
+/// Positions outside of the screen map to the black border.
+fn border_index(position: f32, size: usize) -> usize {
+    (position.max(-1.0).min(size as f32) + 1.0) as usize
+}
+
+fn blend(a: u32, b: u32, weight: u32) -> u32 {
+    let red_blue = (((a & 0xff00ff) * (256 - weight) + (b & 0xff00ff) * weight) >> 8) & 0xff00ff;
+    let green = (((a & 0xff00) * (256 - weight) + (b & 0xff00) * weight) >> 8) & 0xff00;
+    0xff000000 | red_blue | green
+}
+
+fn scale_brightness(color: u32, brightness: u32) -> u32 {
+    let channel = |shift: u32| ((((color >> shift) & 0xff) * brightness) >> 8).min(255) << shift;
+    0xff000000 | channel(16) | channel(8) | channel(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tap(position: usize, start: f32, pixel_size: f32) -> (usize, usize, u32) {
+        let tap = Tap::new(position, start, pixel_size, 320);
+        (tap.a, tap.b, tap.weight)
+    }
+
+    #[test]
+    fn tap_inside_pixel_has_no_weight() {
+        assert_eq!(tap(0, 0.0, 2.0), (1, 2, 0));
+        assert_eq!(tap(1, 0.0, 2.0), (1, 2, 0));
+        assert_eq!(tap(2, 0.0, 2.0), (2, 3, 0));
+    }
+
+    #[test]
+    fn tap_across_pixel_boundary_blends() {
+        assert_eq!(tap(1, 0.0, 1.5), (1, 2, 128));
+        assert_eq!(tap(1, 0.0, 4.0 / 3.0), (1, 2, 170));
+    }
+
+    #[test]
+    fn tap_outside_screen_uses_border() {
+        assert_eq!(tap(0, 10.0, 2.0), (0, 0, 0));
+        assert_eq!(tap(9, 9.5, 1.0), (0, 1, 128));
+        assert_eq!(tap(700, 10.0, 2.0), (321, 321, 0));
+    }
+
+    #[test]
+    fn blend_mixes_channels() {
+        assert_eq!(blend(0xffff0000, 0xff0000ff, 0), 0xffff0000);
+        assert_eq!(blend(0xffff0000, 0xff0000ff, 256), 0xff0000ff);
+        assert_eq!(blend(0xffff0000, 0xff0000ff, 128), 0xff7f007f);
+        assert_eq!(blend(0xff00ff00, 0xff000000, 128), 0xff007f00);
+    }
+
+    #[test]
+    fn scale_brightness_clamps() {
+        assert_eq!(scale_brightness(0xffffffff, 128), 0xff7f7f7f);
+        assert_eq!(scale_brightness(0xff8040ff, 512), 0xffff80ff);
+    }
+}
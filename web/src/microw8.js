@@ -29,10 +29,43 @@ export default function MicroW8(screen, config = {}) {
     let currentData;
     
     let pad = 0;
+
+    let mouseX = 0;
+    let mouseY = 0;
+    let mouseButtons = 0;
+    let pointerHandler = (e) => {
+        // the client area excludes the border of the canvas
+        let rect = screen.getBoundingClientRect();
+        mouseX = Math.floor((e.clientX - rect.left - screen.clientLeft) * 320 / screen.clientWidth);
+        mouseY = Math.floor((e.clientY - rect.top - screen.clientTop) * 240 / screen.clientHeight);
+        // only presses starting on the canvas count, capturing the pointer keeps
+        // reporting the buttons until they are released outside of it
+        if (e.type == 'pointerdown') {
+            screen.setPointerCapture(e.pointerId);
+        }
+        if (screen.hasPointerCapture(e.pointerId)) {
+            // e.buttons uses the same bits as MicroW8: left, right, middle
+            mouseButtons = e.buttons & 7;
+        }
+    };
+    window.addEventListener('pointermove', pointerHandler);
+    screen.addEventListener('pointerdown', pointerHandler);
+    screen.addEventListener('pointerup', pointerHandler);
+    screen.addEventListener('lostpointercapture', () => mouseButtons = 0);
+    screen.addEventListener('contextmenu', (e) => e.preventDefault());
+
+    let typedChars = [];
     let keyboardElement = config.keyboardElement == undefined ? screen : config.keyboardElement;
     if(keyboardElement) {
         let keyHandler = (e) => {
             let isKeyDown = e.type == 'keydown';
+            if (isKeyDown) {
+                // same character set as the native runtime: newline, backspace and latin-1
+                let c = e.key == 'Enter' ? 10 : e.key == 'Backspace' ? 8 : e.key.length == 1 ? e.key.charCodeAt(0) : 0;
+                if (c == 8 || c == 10 || (c >= 32 && c < 127) || (c >= 160 && c < 256)) {
+                    typedChars.push(c);
+                }
+            }
             let mask;
             switch (e.code) {
                 case 'ArrowUp':
@@ -308,6 +341,20 @@ export default function MicroW8(screen, config = {}) {
                         u32Mem[16] = time;
                         u32Mem[17] = pad | gamepad;
                         u32Mem[18] = frameCounter++;
+                        let i16Mem = new Int16Array(memory.buffer, 112, 2);
+                        i16Mem[0] = Math.max(-32768, Math.min(32767, mouseX));
+                        i16Mem[1] = Math.max(-32768, Math.min(32767, mouseY));
+                        memU8[116] = mouseButtons;
+                        let keyboardWrite = memU8[0x13c01];
+                        for (let c of typedChars) {
+                            if (((keyboardWrite + 1) & 255) == memU8[0x13c00]) {
+                                break;
+                            }
+                            memU8[0x13d00 + keyboardWrite] = c;
+                            keyboardWrite = (keyboardWrite + 1) & 255;
+                        }
+                        memU8[0x13c01] = keyboardWrite;
+                        typedChars = [];
                         if(instance.exports.upd) {
                             instance.exports.upd();
                         }
@@ -14,6 +14,11 @@ events.onmessage = event => {
     console.log(event.data);
     if(event.data == 'L') {
         uw8.runModuleFromURL('cart', true);
+    } else if(event.data[0] == 'E') {
+        uw8.runModule(new ArrayBuffer(0));
+        let elem = document.getElementById('message');
+        elem.innerText = event.data.slice(1);
+        elem.hidden = false;
     }
 };
 uw8.runModuleFromURL('cart', true);